@@ -2,11 +2,17 @@ use std::f32::consts::E;
 
 use serde::{Serialize, Deserialize};
 
+/// SELU negative saturation
+pub const SELU_ALPHA: f32 = 1.673_263_2;
+/// SELU output scale
+pub const SELU_SCALE: f32 = 1.050_701;
+
 #[derive(Serialize, Deserialize, Clone, Copy)]
 pub enum Act {
     Tanh,
     Sig,
-    Lin
+    Lin,
+    Selu
 }
 
 fn sigmoid(n: f32) -> f32 {
@@ -19,7 +25,10 @@ impl Act {
         match self {
             Act::Tanh => n.tanh(),
             Act::Sig  => sigmoid(n),
-            Act::Lin  => n
+            Act::Lin  => n,
+            Act::Selu => {
+                if n > 0.0 { SELU_SCALE * n } else { SELU_SCALE * SELU_ALPHA * n.exp_m1() }
+            }
         }
     }
 
@@ -32,6 +41,9 @@ impl Act {
                 sig * (1.0 - sig)
             }
             Act::Lin  => 1.0,
+            Act::Selu => {
+                if n > 0.0 { SELU_SCALE } else { SELU_SCALE * SELU_ALPHA * n.exp() }
+            }
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,34 @@
+use serde::{Serialize, Deserialize};
+
+use crate::matrix::Mat;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum Bias {
+    Value(f32),
+    Range(
+        f32, // min
+        f32 // max
+    ),
+    Normal(
+        f32, // mean
+        f32 // std
+    ),
+    /// Log class priors, matching a softmax output to the label frequencies
+    Prior(Vec<f32>)
+}
+
+impl Bias {
+    /// Creates biases given `n_out` nodes
+    pub fn init(&self, n_out: usize) -> Mat {
+        match self {
+            Bias::Value(n) => Mat::filled((n_out, 1), *n),
+            Bias::Range(min, max) => Mat::random((n_out, 1), *min, *max),
+            Bias::Normal(mean, std) => Mat::random_normal((n_out, 1), *mean, *std),
+            Bias::Prior(priors) => {
+                assert_eq!(priors.len(), n_out);
+                let buf = priors.iter().map(|p| p.ln()).collect();
+                Mat::from_vec((n_out, 1), buf)
+            }
+        }
+    }
+}
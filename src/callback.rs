@@ -0,0 +1,100 @@
+use crate::{
+    history::{Epoch, History},
+    network::FeedForward
+};
+
+/// Requested continuation of training
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Control {
+    #[default]
+    Continue,
+    /// Stops training after the current batch or epoch
+    Stop
+}
+
+/// Training statistics of a single batch
+#[derive(Clone, Copy, Debug)]
+pub struct Batch {
+    /// Index of the batch within its epoch
+    pub index: usize,
+    /// Mean batch loss
+    pub loss: f32,
+    /// Batch accuracy
+    pub accuracy: f32,
+    /// Applied learn rate
+    pub learn_rate: f32,
+    /// Pre-clip global gradient norm
+    pub grad_norm: f32
+}
+
+/// Hooks observing and steering `FeedForward::train`
+///
+/// ## Note
+/// Every hook defaults to a no-op that continues training
+pub trait Callback<const L: usize>: Send {
+    fn on_train_begin(&mut self, _net: &mut FeedForward<L>) {}
+
+    fn on_epoch_begin(&mut self, _net: &mut FeedForward<L>, _epoch: usize) -> Control {
+        Control::Continue
+    }
+
+    fn on_batch_end(&mut self, _net: &mut FeedForward<L>, _batch: &Batch) -> Control {
+        Control::Continue
+    }
+
+    fn on_epoch_end(&mut self, _net: &mut FeedForward<L>, _epoch: usize, _stats: &Epoch) -> Control {
+        Control::Continue
+    }
+
+    /// Called when early stopping ends training after `epoch`
+    fn on_early_stop(&mut self, _net: &mut FeedForward<L>, _epoch: usize) {}
+
+    /// Called before the weights of the best `epoch` are restored
+    fn on_restore_best(&mut self, _net: &mut FeedForward<L>, _epoch: usize) {}
+
+    fn on_train_end(&mut self, _net: &mut FeedForward<L>, _history: &History) {}
+}
+
+/// Prints the stats of every epoch
+///
+/// ## Note
+/// Installed by `train` when the model is `verbose`
+#[derive(Default)]
+pub struct Logger {
+    epochs: usize
+}
+
+impl<const L: usize> Callback<L> for Logger {
+    fn on_train_begin(&mut self, net: &mut FeedForward<L>) {
+        self.epochs = net.params().epochs;
+    }
+
+    fn on_epoch_begin(&mut self, _net: &mut FeedForward<L>, epoch: usize) -> Control {
+        println!("epoch {} of {}", epoch+1, self.epochs);
+        Control::Continue
+    }
+
+    fn on_epoch_end(&mut self, _net: &mut FeedForward<L>, _epoch: usize, stats: &Epoch) -> Control {
+        println!("finished in {}s", stats.elapsed);
+        println!("loss: {}", stats.loss);
+        println!("accuracy: {}", stats.accuracy);
+        println!("learn rate: {}", stats.learn_rate);
+        println!("gradient norm: {}", stats.grad_norm);
+        println!("throughput: {} samples/s", stats.throughput);
+
+        if let (Some(loss), Some(accuracy)) = (stats.valid_loss, stats.valid_accuracy) {
+            println!("valid loss: {}", loss);
+            println!("valid accuracy: {}", accuracy);
+        }
+
+        Control::Continue
+    }
+
+    fn on_early_stop(&mut self, _net: &mut FeedForward<L>, epoch: usize) {
+        println!("stopping early after epoch {}", epoch+1);
+    }
+
+    fn on_restore_best(&mut self, _net: &mut FeedForward<L>, epoch: usize) {
+        println!("restoring weights of epoch {}", epoch+1);
+    }
+}
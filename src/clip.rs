@@ -0,0 +1,30 @@
+use serde::{Serialize, Deserialize};
+
+use crate::matrix::Mat;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum Clip {
+    /// Clamps every gradient element to `[-max, max]`
+    Value(
+        f32 // max
+    ),
+    /// Rescales the gradients of all layers so their
+    /// global L2 norm is at most `max`
+    Norm(
+        f32 // max
+    )
+}
+
+impl Clip {
+    /// Clips `grad` given the global gradient `norm`
+    pub fn apply(&self, grad: &mut Mat, norm: f32) {
+        match self {
+            Clip::Value(max) => grad.map_assign(|n| *n = n.clamp(-max, *max)),
+            Clip::Norm(max) => {
+                if norm > *max {
+                    grad.scale_assign(max / norm);
+                }
+            }
+        }
+    }
+}
@@ -1,23 +1,182 @@
+use std::f32::consts::LN_2;
 use serde::{Serialize, Deserialize};
+use crate::{
+    matrix::{Mat, MatBase},
+    loss::Loss
+};
 
+/// Smallest probability passed to a logarithm
+const EPSILON: f32 = 1e-7;
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
 pub enum Cost {
-    MSE
+    MSE,
+    /// Categorical cross-entropy fused with a softmax output
+    CrossEntropy,
+    /// Binary cross-entropy fused with a sigmoid output
+    BinaryCrossEntropy,
+    /// Negative log-likelihood fused with a log-softmax output
+    NLL,
+    /// Mean absolute error
+    MAE,
+    Huber(
+        f32 // delta
+    ),
+    LogCosh,
+    /// Hinge loss over labels in `{-1, 1}`
+    Hinge,
+    /// Squared hinge loss over labels in `{-1, 1}`
+    SquaredHinge,
+    /// Kullback-Leibler divergence from label distribution `y`
+    KLDivergence,
+    /// Pinball loss of quantile `tau`
+    Quantile(
+        f32 // tau
+    )
+}
+
+/// Computes `ln Σ eᶻ` without overflowing
+fn log_sum_exp(z: &Mat) -> f32 {
+    let max = z.data()
+        .iter()
+        .copied()
+        .fold(f32::NEG_INFINITY, f32::max);
+
+    let sum: f32 = z.data()
+        .iter()
+        .map(|n| (n - max).exp())
+        .sum();
+
+    max + sum.ln()
+}
+
+/// Computes the logistic function without overflowing
+fn sigmoid(n: f32) -> f32 {
+    if n >= 0.0 {
+        1.0 / (1.0 + (-n).exp())
+    } else {
+        let exp = n.exp();
+        exp / (1.0 + exp)
+    }
 }
 
 impl Cost {
-    /// Applies cost function to `err`
-    pub fn value(&self, err: f32) -> f32 {
+    /// Applies cost function to prediction `a` against label `y`
+    pub fn value(&self, y: f32, a: f32) -> f32 {
+        match self {
+            Cost::MSE => (y - a).powi(2),
+            Cost::CrossEntropy => -y * a.max(EPSILON).ln(),
+            Cost::BinaryCrossEntropy => {
+                -y * a.max(EPSILON).ln() - (1.0 - y) * (1.0 - a).max(EPSILON).ln()
+            }
+            Cost::NLL => -y * a,
+            Cost::MAE => (y - a).abs(),
+            Cost::Huber(delta) => {
+                let err = (y - a).abs();
+
+                if err <= *delta {
+                    0.5 * err.powi(2)
+                } else {
+                    delta * (err - 0.5 * delta)
+                }
+            }
+            Cost::LogCosh => {
+                // ln(cosh(x)) = |x| + ln(1 + e^-2|x|) - ln(2)
+                let err = (y - a).abs();
+                err + (-2.0 * err).exp().ln_1p() - LN_2
+            }
+            Cost::Hinge => (1.0 - y * a).max(0.0),
+            Cost::SquaredHinge => (1.0 - y * a).max(0.0).powi(2),
+            Cost::KLDivergence => {
+                if y > 0.0 {
+                    y * (y / a.max(EPSILON)).ln()
+                } else {
+                    0.0
+                }
+            }
+            Cost::Quantile(tau) => {
+                let err = y - a;
+                f32::max(tau * err, (tau - 1.0) * err)
+            }
+        }
+    }
+
+    /// Applies cost derivative to prediction `a` against label `y`
+    ///
+    /// ## Note
+    /// Fused costs differentiate w.r.t. the output pre-activation `Z ₗ`
+    pub fn deriv(&self, y: f32, a: f32) -> f32 {
         match self {
-            Cost::MSE => err.powi(2)
+            Cost::MSE => 2.0 * (a - y),
+            Cost::CrossEntropy | Cost::BinaryCrossEntropy => a - y,
+            Cost::NLL => a.exp() - y,
+            Cost::MAE => {
+                if a == y { 0.0 } else { (a - y).signum() }
+            }
+            Cost::Huber(delta) => (a - y).clamp(-delta, *delta),
+            Cost::LogCosh => (a - y).tanh(),
+            Cost::Hinge => {
+                if y * a < 1.0 { -y } else { 0.0 }
+            }
+            Cost::SquaredHinge => -2.0 * y * (1.0 - y * a).max(0.0),
+            Cost::KLDivergence => -y / a.max(EPSILON),
+            Cost::Quantile(tau) => {
+                if y > a { -tau } else { 1.0 - tau }
+            }
         }
     }
+}
+
+impl Loss for Cost {
+    /// Sums the cost of every output of `a` against `y`
+    ///
+    /// ## Equations
+    /// - `CE( Z ) = Σ y (lse( Z ) - z)`, as is `NLL( Z )`
+    /// - `BCE( Z ) = Σ max(z, 0) - y z + ln(1 + e^-|z|)`
+    fn loss(&self, y: &Mat, a: &Mat) -> f32 {
+        let pairs = y.data().iter().zip(a.data());
+
+        match self {
+            Cost::CrossEntropy | Cost::NLL => {
+                let lse = log_sum_exp(a);
+                pairs.map(|(y, z)| y * (lse - z)).sum()
+            }
+            Cost::BinaryCrossEntropy => pairs
+                .map(|(y, z)| z.max(0.0) - y * z + (-z.abs()).exp().ln_1p())
+                .sum(),
+            _ => pairs.map(|(y, a)| self.value(*y, *a)).sum()
+        }
+    }
+
+    fn grad(&self, y: &Mat, a: &Mat) -> Mat {
+        let buf = y.data()
+            .iter()
+            .zip(a.data())
+            .map(|(y, a)| self.deriv(*y, *a))
+            .collect();
+
+        Mat::from_vec(a.shape(), buf)
+    }
+
+    fn is_fused(&self) -> bool {
+        matches!(self, Cost::CrossEntropy | Cost::BinaryCrossEntropy | Cost::NLL)
+    }
 
-    /// Applies cost derivative to `err`
-    pub fn deriv(&self, err: f32) -> f32 {
+    /// ## Equations
+    /// - `softmax( Z ) = e^( Z - lse( Z ) )`
+    /// - `log_softmax( Z ) = Z - lse( Z )`
+    fn link(&self, z: &Mat) -> Option<Mat> {
         match self {
-            Cost::MSE => 2.0 * err
+            Cost::CrossEntropy => {
+                let lse = log_sum_exp(z);
+                Some(z.map(|n| (n - lse).exp()))
+            }
+            Cost::BinaryCrossEntropy => Some(z.map(sigmoid)),
+            Cost::NLL => {
+                let lse = log_sum_exp(z);
+                Some(z.map(|n| n - lse))
+            }
+            _ => None
         }
     }
 }
\ No newline at end of file
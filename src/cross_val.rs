@@ -0,0 +1,169 @@
+use std::{io::Error, path::Path};
+use rand::seq::SliceRandom;
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    matrix::Mat,
+    parameters::Params,
+    rng::Pcg,
+    stopping::Metric
+};
+
+/// Held out statistics of a single fold
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Fold {
+    /// Indices of the held out samples
+    pub held_out: Vec<usize>,
+    /// Mean held out loss
+    pub loss: f32,
+    /// Held out accuracy
+    pub accuracy: f32
+}
+
+/// Statistics of a cross-validation run
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct CrossVal {
+    pub folds: Vec<Fold>,
+    /// Scored metric
+    pub metric: Metric,
+    /// Mean of the metric over folds
+    pub mean: f32,
+    /// Standard deviation of the metric over folds
+    pub std: f32
+}
+
+/// K-fold cross-validation builder
+pub struct KFold {
+    k: usize,
+    stratified: bool,
+    save_dir: Option<String>
+}
+
+impl KFold {
+    pub fn new(k: usize) -> Self {
+        assert!(k > 1);
+
+        Self {
+            k,
+            stratified: false,
+            save_dir: None
+        }
+    }
+
+    /// Set whether folds preserve the label distribution
+    pub fn stratified(&mut self, stratified: bool) -> &mut Self {
+        self.stratified = stratified;
+        self
+    }
+
+    /// Set the directory each fold's model is saved to, creating it
+    pub fn save_models(&mut self, save_dir: &str) -> &mut Self {
+        self.save_dir = Some(save_dir.to_string());
+        self
+    }
+
+    /// Trains a fresh model from `params` on every fold of inputs `xs`
+    /// and labels `ys`, scoring `metric` on the held out samples
+    pub fn run<const L: usize>(
+        &self,
+        params: &Params<L>,
+        (xs, ys): (&[Mat], &[Mat]),
+        metric: Metric
+    ) -> Result<CrossVal, Error> {
+        assert_eq!(xs.len(), ys.len());
+        assert!(xs.len() >= self.k);
+
+        let folds = self.assign(params, ys);
+        let mut stats = Vec::with_capacity(self.k);
+
+        if let Some(dir) = &self.save_dir {
+            std::fs::create_dir_all(std::env::current_dir()?.join(dir))?;
+        }
+
+        for fold in 0..self.k {
+            let (mut train_xs, mut train_ys) = (Vec::new(), Vec::new());
+            let (mut test_xs, mut test_ys) = (Vec::new(), Vec::new());
+            let mut held_out = Vec::new();
+
+            for (i, &f) in folds.iter().enumerate() {
+                if f == fold {
+                    held_out.push(i);
+                    test_xs.push(xs[i].clone());
+                    test_ys.push(ys[i].clone());
+                } else {
+                    train_xs.push(xs[i].clone());
+                    train_ys.push(ys[i].clone());
+                }
+            }
+
+            let mut params = params.clone();
+            if let Some(dir) = &self.save_dir {
+                params.save_path = Path::new(dir)
+                    .join(format!("fold_{}", fold))
+                    .to_string_lossy()
+                    .to_string();
+            }
+
+            let mut net = params.build();
+            net.train((&train_xs, &train_ys), None)?;
+
+            let (loss, accuracy) = net.evaluate((&test_xs, &test_ys));
+            if params.verbose {
+                println!("fold {} of {}: loss {} accuracy {}", fold+1, self.k, loss, accuracy);
+            }
+            if self.save_dir.is_some() {
+                net.save_model()?;
+            }
+
+            stats.push(Fold { held_out, loss, accuracy });
+        }
+
+        let scores: Vec<_> = stats
+            .iter()
+            .map(|fold| match metric {
+                Metric::Loss => fold.loss,
+                Metric::Accuracy => fold.accuracy
+            })
+            .collect();
+
+        let mean = scores.iter().sum::<f32>() / self.k as f32;
+        let var = scores.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / self.k as f32;
+
+        Ok(CrossVal {
+            folds: stats,
+            metric,
+            mean,
+            std: var.sqrt()
+        })
+    }
+
+    /// Assigns every sample a fold, dealing each label's samples
+    /// round-robin when `stratified`
+    fn assign<const L: usize>(&self, params: &Params<L>, ys: &[Mat]) -> Vec<usize> {
+        let mut rng = params.seed.map_or_else(Pcg::from_entropy, Pcg::new);
+        let mut order: Vec<_> = (0..ys.len()).collect();
+        order.shuffle(&mut rng);
+
+        if self.stratified {
+            // stable sort keeps the shuffle within each label
+            order.sort_by_key(|&i| ys[i].max_index().0);
+        }
+
+        let mut folds = vec![0; ys.len()];
+        for (position, &i) in order.iter().enumerate() {
+            folds[i] = position % self.k;
+        }
+
+        folds
+    }
+}
+
+/// Cross-validates `params` over `k` folds of inputs `xs` and labels `ys`
+pub fn cross_validate<const L: usize>(
+    params: &Params<L>,
+    data: (&[Mat], &[Mat]),
+    k: usize,
+    metric: Metric
+) -> Result<CrossVal, Error> {
+    KFold::new(k).run(params, data, metric)
+}
@@ -1,4 +1,4 @@
-use std::{path::PathBuf, fs};
+use std::{path::Path, fs};
 use serde::{Serialize, Deserialize};
 use crate::matrix::{Mat, MatBase};
 use super::dataset::Dataset;
@@ -27,6 +27,7 @@ use super::dataset::Dataset;
 /// ...
 /// xxxx       ??               pixel
 
+#[allow(clippy::empty_line_after_doc_comments)]
 const TRAIN_LABELS_PATH: &str = "src/res/train-labels";
 const TRAIN_IMAGES_PATH: &str = "src/res/train-images";
 const TEST_LABELS_PATH: &str =  "src/res/test_labels";
@@ -90,7 +91,7 @@ impl Dataset for Reader {
 }
 
 impl Reader {
-    fn read_labels(data_type: DataType, work_dir: &PathBuf) -> Vec<Mat> {
+    fn read_labels(data_type: DataType, work_dir: &Path) -> Vec<Mat> {
         let path = match data_type {
             DataType::Train => TRAIN_LABELS_PATH,
             DataType::Test =>  TEST_LABELS_PATH
@@ -111,7 +112,7 @@ impl Reader {
             .collect()
     }
 
-    fn read_images(data_type: DataType, work_dir: &PathBuf) -> Vec<Mat> {
+    fn read_images(data_type: DataType, work_dir: &Path) -> Vec<Mat> {
         let path = match data_type {
             DataType::Train => TRAIN_IMAGES_PATH,
             DataType::Test =>  TEST_IMAGES_PATH
@@ -140,12 +141,10 @@ impl Reader {
     }
 
     pub fn print_image(&self, data_type: DataType, index: usize) {
-        let images;
-        
-        match data_type {
-            DataType::Train => images = &self.train_images,
-            DataType::Test  => images = &self.test_images
-        }
+        let images = match data_type {
+            DataType::Train => &self.train_images,
+            DataType::Test  => &self.test_images
+        };
 
         for (i, byte) in images[index].data().iter().enumerate() {
             if *byte > 0.8 {
@@ -0,0 +1,163 @@
+use std::io::Error;
+use crate::{
+    cost::Cost,
+    history::History,
+    loss::Loss,
+    matrix::{Mat, MatBase},
+    network::FeedForward,
+    parameters::Params
+};
+
+/// Softmax loss against hard labels stacked on a teacher's softened outputs
+///
+/// ## Equations
+/// - `L = α CE(y, a) + (1 - α) T² KL(p_T || a_T)`
+/// - `∂L/∂Z = α (a - y) + (1 - α) T (a_T - p_T)`
+///
+/// ## Note
+/// - Targets hold the hard labels followed by the soft targets `p_T`
+/// - Targets of only hard labels reduce to cross entropy
+pub struct Distillation {
+    pub temperature: f32,
+    pub alpha: f32
+}
+
+impl Distillation {
+    /// Softens logits `z` to `temperature`
+    ///
+    /// ## Equations
+    /// - `p_T = softmax(z / T)`
+    pub fn soften(z: &Mat, temperature: f32) -> Mat {
+        let scaled = z.scale(1.0 / temperature);
+        Cost::CrossEntropy.link(&scaled).unwrap_or(scaled)
+    }
+
+    /// Measures the cross entropy of distribution `p`
+    /// against the logits `z` softened to `temperature`
+    fn cross_entropy(p: &[f32], z: &Mat, temperature: f32) -> f32 {
+        let p = Mat::from_vec(z.shape(), p.to_vec());
+        Cost::CrossEntropy.loss(&p, &z.scale(1.0 / temperature))
+    }
+
+    /// Returns the hard labels of `y` and its soft targets, if stacked
+    fn split<'a>(y: &'a Mat, a: &Mat) -> (&'a [f32], Option<&'a [f32]>) {
+        let n = a.row();
+
+        match y.row() == 2 * n {
+            true => (&y.data()[..n], Some(&y.data()[n..])),
+            false => (y.data(), None)
+        }
+    }
+}
+
+impl Loss for Distillation {
+    /// ## Equations
+    /// - `KL(p || q) = H(p, q) - H(p)`
+    fn loss(&self, y: &Mat, z: &Mat) -> f32 {
+        let (hard, soft) = Self::split(y, z);
+        let ce = Self::cross_entropy(hard, z, 1.0);
+
+        let Some(soft) = soft else {
+            return ce
+        };
+
+        let t = self.temperature;
+        let entropy: f32 = soft
+            .iter()
+            .filter(|p| **p > 0.0)
+            .map(|p| -p * p.ln())
+            .sum();
+
+        let kl = Self::cross_entropy(soft, z, t) - entropy;
+        self.alpha * ce + (1.0 - self.alpha) * t * t * kl
+    }
+
+    fn grad(&self, y: &Mat, a: &Mat) -> Mat {
+        let (hard, soft) = Self::split(y, a);
+        let hard_grad = a.data().iter().zip(hard).map(|(a, y)| a - y);
+
+        let Some(soft) = soft else {
+            return Mat::from_vec(a.shape(), hard_grad.collect())
+        };
+
+        // `ln a` only differs from the logits by a constant
+        let t = self.temperature;
+        let softened = Self::soften(&a.map(f32::ln), t);
+        let buf = hard_grad
+            .zip(softened.data().iter().zip(soft))
+            .map(|(hard, (q, p))| self.alpha * hard + (1.0 - self.alpha) * t * (q - p))
+            .collect();
+
+        Mat::from_vec(a.shape(), buf)
+    }
+
+    fn is_fused(&self) -> bool {
+        true
+    }
+
+    fn link(&self, z: &Mat) -> Option<Mat> {
+        Cost::CrossEntropy.link(z)
+    }
+}
+
+/// Result of distilling a teacher into a student
+pub struct Distilled<const L: usize> {
+    pub student: FeedForward<L>,
+    pub history: History,
+    /// Student accuracy over the held out labels
+    pub accuracy: f32,
+    /// Teacher accuracy over the held out labels
+    pub teacher_accuracy: f32
+}
+
+/// Trains a student built from `params` on inputs `xs`, learning from
+/// both labels `ys` and the `teacher`'s logits softened to `temperature`,
+/// weighting the hard labels by `alpha`, then compares both on the `test` set
+///
+/// ## Note
+/// - Any teacher works, as its output pre-activation is softened
+/// - The student falls back to cross entropy once saved
+pub fn distill<const T: usize, const S: usize>(
+    teacher: &mut FeedForward<T>,
+    params: &Params<S>,
+    (xs, ys): (&[Mat], &[Mat]),
+    test: (&[Mat], &[Mat]),
+    temperature: f32,
+    alpha: f32
+) -> Result<Distilled<S>, Error> {
+    assert_eq!(xs.len(), ys.len());
+    assert!(temperature > 0.0 && (0.0..=1.0).contains(&alpha));
+
+    // hard labels stacked on the softened teacher outputs
+    let targets: Vec<_> = xs
+        .iter()
+        .zip(ys)
+        .map(|(x, y)| {
+            let soft = Distillation::soften(&teacher.logits(x), temperature);
+            let buf = y.data().iter().chain(soft.data()).copied().collect();
+            Mat::from_vec((2 * y.row(), 1), buf)
+        })
+        .collect();
+
+    let mut student = params
+        .clone()
+        .cost(Cost::CrossEntropy)
+        .loss(Distillation { temperature, alpha })
+        .build();
+
+    let history = student.train((xs, &targets), None)?;
+    let accuracy = student.accuracy(test);
+    let teacher_accuracy = teacher.accuracy(test);
+
+    if params.verbose {
+        println!("student accuracy: {}", accuracy);
+        println!("teacher accuracy: {}", teacher_accuracy);
+    }
+
+    Ok(Distilled {
+        student,
+        history,
+        accuracy,
+        teacher_accuracy
+    })
+}
@@ -64,7 +64,7 @@ fn model(app: &App) -> Model {
 }
 
 fn update(app: &App, model: &mut Model, _update: Update) {
-    let input = Mat::from_arr(model.buf.map(|n| n as f32));
+    let input = Mat::from_arr(model.buf);
     model.out = model.digit_model.predict(&input);
 
     if !(model.l_mouse_pressed || model.r_mouse_pressed) {
@@ -89,7 +89,7 @@ fn update(app: &App, model: &mut Model, _update: Update) {
                 }
 
                 if model.l_mouse_pressed {
-                    *pixel = (*pixel + 1.0 / dist as f32).clamp(0.0, 1.0);
+                    *pixel = (*pixel + 1.0 / dist).clamp(0.0, 1.0);
                 }
                 else {
                     *pixel = 0.0;
@@ -114,14 +114,7 @@ fn view(app: &App, model: &Model, frame: Frame) {
     for (i, (d, p)) in out.iter().enumerate() {
         let s = format!("\"{}\": {:.2}%", d, *p * 100.);
 
-        let font_size;
-
-        if i == 0 {
-            font_size = 40;
-        }
-        else {
-            font_size = 35;
-        }
+        let font_size = if i == 0 { 40 } else { 35 };
 
         draw
             .text(&s)
@@ -178,16 +171,8 @@ fn key_press(_: &App, model: &mut Model, key: Key) {
                 .try_into()
                 .unwrap()
         }
-        Key::Up => {
-            if model.draw_radius <= 5 {
-                model.draw_radius += 1
-            }
-        }
-        Key::Down => {
-            if model.draw_radius > 1 {
-                model.draw_radius -= 1
-            }
-        }
+        Key::Up if model.draw_radius <= 5 => model.draw_radius += 1,
+        Key::Down if model.draw_radius > 1 => model.draw_radius -= 1,
         _ => ()
     }
 }
@@ -0,0 +1,50 @@
+use rand::Rng;
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    activation::{SELU_ALPHA, SELU_SCALE},
+    matrix::Mat
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum Dropout {
+    /// Inverted dropout of drop `rate`
+    Standard(
+        f32 // rate
+    ),
+    /// Alpha dropout of drop `rate`, preserving SELU self-normalization
+    Alpha(
+        f32 // rate
+    )
+}
+
+impl Dropout {
+    /// Samples a dropout mask of `shape` from `rng`, returning `(scale, shift)`
+    /// such that the dropped activation is `A . scale + shift`
+    pub fn mask<R: Rng>(&self, shape: (usize, usize), rng: &mut R) -> (Mat, Mat) {
+        let mut bernoulli = |keep: f32| -> Mat {
+            let buf = (0..shape.0*shape.1)
+                .map(|_| if rng.gen::<f32>() < keep { 1.0 } else { 0.0 })
+                .collect();
+
+            Mat::from_vec(shape, buf)
+        };
+
+        match self {
+            Dropout::Standard(rate) => {
+                let keep = 1.0 - rate;
+                (bernoulli(keep).scale(1.0 / keep), Mat::zeros(shape))
+            }
+            Dropout::Alpha(rate) => {
+                // dropped activations saturate to the negative SELU limit
+                let alpha = -SELU_SCALE * SELU_ALPHA;
+                let keep = 1.0 - rate;
+                let a = (keep + alpha.powi(2) * keep * rate).powf(-0.5);
+                let b = -a * alpha * rate;
+
+                let kept = bernoulli(keep);
+                (kept.scale(a), kept.map(|k| a * alpha * (1.0 - k) + b))
+            }
+        }
+    }
+}
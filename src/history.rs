@@ -0,0 +1,96 @@
+use std::{fmt::Write, io::Error, path::Path};
+use serde::{Serialize, Deserialize};
+
+/// Training statistics of a single epoch
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Epoch {
+    /// Mean training loss
+    pub loss: f32,
+    /// Training accuracy
+    pub accuracy: f32,
+    /// Mean validation loss
+    pub valid_loss: Option<f32>,
+    /// Validation accuracy
+    pub valid_accuracy: Option<f32>,
+    /// Learn rate of the final batch
+    pub learn_rate: f32,
+    /// Mean pre-clip global gradient norm
+    pub grad_norm: f32,
+    /// Wall time of the epoch in seconds
+    pub elapsed: f32,
+    /// Trained samples per second
+    pub throughput: f32
+}
+
+/// Training statistics of a whole run
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct History {
+    pub epochs: Vec<Epoch>
+}
+
+impl History {
+    /// Header of the CSV export
+    const CSV_HEADER: &'static str =
+        "epoch,loss,accuracy,valid_loss,valid_accuracy,learn_rate,grad_norm,elapsed,throughput";
+
+    pub fn len(&self) -> usize {
+        self.epochs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.epochs.is_empty()
+    }
+
+    /// Returns the stats of the final epoch
+    pub fn last(&self) -> Option<&Epoch> {
+        self.epochs.last()
+    }
+
+    /// Total wall time of the run in seconds
+    pub fn elapsed(&self) -> f32 {
+        self.epochs.iter().map(|epoch| epoch.elapsed).sum()
+    }
+
+    /// Serializes the history to JSON
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Serializes the history to CSV with one row per epoch
+    ///
+    /// ## Note
+    /// Missing validation stats are left empty
+    pub fn to_csv(&self) -> String {
+        let opt = |n: Option<f32>| n.map(|n| n.to_string()).unwrap_or_default();
+        let mut csv = String::from(Self::CSV_HEADER);
+
+        for (i, epoch) in self.epochs.iter().enumerate() {
+            // writing to a `String` can't fail
+            let _ = write!(
+                csv,
+                "\n{},{},{},{},{},{},{},{},{}",
+                i+1,
+                epoch.loss,
+                epoch.accuracy,
+                opt(epoch.valid_loss),
+                opt(epoch.valid_accuracy),
+                epoch.learn_rate,
+                epoch.grad_norm,
+                epoch.elapsed,
+                epoch.throughput
+            );
+        }
+
+        csv
+    }
+
+    /// Saves the history as JSON to `path`
+    pub fn save_json<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_json()?)
+    }
+
+    /// Saves the history as CSV to `path`
+    pub fn save_csv<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        std::fs::write(path, self.to_csv())
+    }
+}
@@ -0,0 +1,32 @@
+use crate::matrix::Mat;
+
+/// A loss over the full prediction of a model
+///
+/// ## Note
+/// `Cost` provides the built-in implementations
+pub trait Loss: Send + Sync {
+    /// Measures the loss of prediction `a` against target `y`
+    ///
+    /// ## Note
+    /// Fused losses measure the output pre-activation `Z ₗ` instead
+    fn loss(&self, y: &Mat, a: &Mat) -> f32;
+
+    /// Computes the loss gradient w.r.t. prediction `a`
+    ///
+    /// ## Note
+    /// Fused losses differentiate w.r.t. the output pre-activation `Z ₗ`
+    fn grad(&self, y: &Mat, a: &Mat) -> Mat;
+
+    /// Whether the loss replaces the output non-linearity
+    ///
+    /// ## Note
+    /// Must agree with `link` returning `Some`
+    fn is_fused(&self) -> bool {
+        false
+    }
+
+    /// Applies the fused output non-linearity to `z`, if any
+    fn link(&self, _z: &Mat) -> Option<Mat> {
+        None
+    }
+}
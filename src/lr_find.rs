@@ -0,0 +1,35 @@
+use serde::{Serialize, Deserialize};
+
+/// Loss curve of a learning rate range test
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct LrCurve {
+    /// Exponentially increasing learn rates
+    pub rates: Vec<f32>,
+    /// Smoothed loss after each rate's batch
+    pub losses: Vec<f32>
+}
+
+impl LrCurve {
+    /// Suggests the rate at which the loss falls the steepest
+    ///
+    /// ## Note
+    /// Rates are spaced evenly in log space, so the steepest
+    /// slope is the most negative loss difference
+    pub fn suggestion(&self) -> Option<f32> {
+        self.losses
+            .windows(2)
+            .map(|w| w[1] - w[0])
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| self.rates[i])
+    }
+
+    /// Returns the rate of the lowest loss
+    pub fn min_loss_rate(&self) -> Option<f32> {
+        self.losses
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(i, _)| self.rates[i])
+    }
+}
@@ -1,37 +1,97 @@
-use activation::Act;
+use cost::Cost;
 use data::{mnist::Reader, dataset::Dataset};
-use matrix::Mat;
 use network::FeedForward;
 use crate::{matrix::MatBase, data::mnist::one_hot};
 
+#[cfg(test)]
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
+#[cfg(test)]
+use activation::Act;
+#[cfg(test)]
+use matrix::Mat;
+#[cfg(test)]
+use loss::Loss;
+#[cfg(test)]
+use weight_init::Weight;
+#[cfg(test)]
+use bias_init::Bias;
+#[cfg(test)]
+use optimizer::{Optim, Optimizer, State};
+#[cfg(test)]
+use schedule::{Schedule, Scheduler};
+#[cfg(test)]
+use clip::Clip;
+#[cfg(test)]
+use penalty::Penalty;
+#[cfg(test)]
+use parameters::Params;
+#[cfg(test)]
+use dropout::Dropout;
+#[cfg(test)]
+use norm::{Norm, NormLayer};
+#[cfg(test)]
+use stopping::Metric;
+#[cfg(test)]
+use callback::{Callback, Control, Batch};
+#[cfg(test)]
+use history::{Epoch, History};
+#[cfg(test)]
+use tuner::{Space, Search, Tuner, Leaderboard};
+#[cfg(test)]
+use cross_val::KFold;
+#[cfg(test)]
+use distill::{distill, Distillation};
+#[cfg(test)]
+use prune::{Prune, Scope};
+
 pub mod parameters;
 pub mod network;
 pub mod data;
 pub mod matrix;
 pub mod back_index;
 pub mod weight_init;
+pub mod bias_init;
 pub mod draw;
 pub mod activation;
+pub mod dropout;
+pub mod norm;
+pub mod callback;
 pub mod cost;
+pub mod clip;
+pub mod penalty;
+pub mod loss;
+pub mod optimizer;
+pub mod schedule;
+pub mod stopping;
+pub mod history;
+pub mod rng;
+pub mod lr_find;
+pub mod tuner;
+pub mod cross_val;
+pub mod distill;
+pub mod prune;
 
 fn main() {
     // train_mnist_to_digit();
     draw::run_sketch();
 }
 
+#[allow(dead_code)]
 fn train_mnist_to_digit() {
     let data = Reader::load_dataset();
 
     let mut net = FeedForward::new([784, 450, 300, 80, 10])
+        .cost(Cost::CrossEntropy)
         .save_path("src/models/test")
         .build();
 
-    net.train(data.train_set());
+    net.train(data.train_set(), None).unwrap();
     net.save_model().unwrap();
 
     println!("acc: {}", net.accuracy(data.test_set()));
 }
 
+#[allow(dead_code)]
 fn train_mnist_to_image() {
     let data = Reader::load_dataset();
 
@@ -39,7 +99,7 @@ fn train_mnist_to_image() {
         .save_path("src/models/test_rev")
         .build();
 
-    net.train((data.train_labels(), data.train_data()));
+    net.train((data.train_labels(), data.train_data()), None).unwrap();
     net.save_model().unwrap();
 
     for i in 0..10 {
@@ -68,8 +128,8 @@ fn train_mnist_to_image() {
 
 #[test]
 fn linear_regression() {
-    let xs = [0.0,  1.0].map(|n| Mat::from_elem(n));
-    let ys = [10.0, 5.0].map(|n| Mat::from_elem(n));
+    let xs = [0.0,  1.0].map(Mat::from_elem);
+    let ys = [10.0, 5.0].map(Mat::from_elem);
 
     let mut nn = FeedForward::new([1, 1])
         .activation(Act::Lin)
@@ -77,10 +137,1083 @@ fn linear_regression() {
         .learn_rate(0.5)
         .build();
 
-    nn.train((&xs, &ys));
+    nn.train((&xs, &ys), None).unwrap();
 
     for i in 0..10 {
         let out = nn.predict(&Mat::from_elem(i as f32));
         println!("({}, {:?})", i, out.data());
     }
 }
+
+#[test]
+fn softmax_cross_entropy() {
+    let xs = [0, 1, 2].map(one_hot);
+    let ys = [2, 0, 1].map(one_hot);
+
+    let mut nn = FeedForward::new([10, 10])
+        .activation(Act::Lin)
+        .cost(Cost::CrossEntropy)
+        .batch_size(1)
+        .learn_rate(0.5)
+        .epochs(20)
+        .verbose(false)
+        .build();
+
+    nn.train((&xs, &ys), None).unwrap();
+
+    let out = nn.predict(&xs[0]);
+    let sum: f32 = out.data().iter().sum();
+
+    assert!((sum - 1.0).abs() < 1e-4);
+    assert_eq!(nn.accuracy((&xs, &ys)), 1.0);
+
+    // the loss from the logits matches the predicted probability
+    let loss = nn.loss((&xs[..1], &ys[..1]));
+    assert!((loss + out[(2, 0)].ln()).abs() < 1e-4);
+}
+
+#[test]
+fn logit_losses() {
+    let y = Mat::from_arr([1.0, 0.0]);
+
+    // confidently wrong predictions don't saturate
+    let ce = Cost::CrossEntropy.loss(&y, &Mat::from_arr([0.0, 40.0]));
+    let nll = Cost::NLL.loss(&y, &Mat::from_arr([0.0, 40.0]));
+    let bce = Cost::BinaryCrossEntropy.loss(&y, &Mat::from_arr([-40.0, 40.0]));
+
+    assert!((ce - 40.0).abs() < 1e-3);
+    assert!((nll - 40.0).abs() < 1e-3);
+    assert!((bce - 80.0).abs() < 1e-3);
+
+    let even = Cost::BinaryCrossEntropy.loss(&y, &Mat::from_arr([0.0, 0.0]));
+    assert!((even - 2.0 * std::f32::consts::LN_2).abs() < 1e-5);
+}
+
+#[test]
+fn robust_cost_gradients() {
+    const H: f32 = 1e-3;
+
+    // (y, a) pairs away from every cost's kinks
+    let regression = [(0.0, 0.3), (1.0, -0.6), (0.5, 2.5), (-1.0, -4.0)];
+    let margins = [(1.0, 0.4), (-1.0, 0.2), (1.0, -1.5), (-1.0, -0.3)];
+    let distributions = [(0.2, 0.5), (0.7, 0.4), (0.1, 0.05)];
+
+    let cases = [
+        (Cost::MAE, &regression[..]),
+        (Cost::Huber(1.0), &regression[..]),
+        (Cost::LogCosh, &regression[..]),
+        (Cost::Quantile(0.8), &regression[..]),
+        (Cost::Hinge, &margins[..]),
+        (Cost::SquaredHinge, &margins[..]),
+        (Cost::KLDivergence, &distributions[..])
+    ];
+
+    for (cost, points) in cases {
+        for &(y, a) in points {
+            let numeric = (cost.value(y, a + H) - cost.value(y, a - H)) / (2.0 * H);
+            let analytic = cost.deriv(y, a);
+
+            assert!(
+                (numeric - analytic).abs() < 1e-2 * analytic.abs().max(1.0),
+                "deriv({}, {}) = {} but finite difference is {}", y, a, analytic, numeric
+            );
+        }
+    }
+}
+
+#[test]
+fn epoch_stats() {
+    let xs: Vec<_> = (0..4).map(one_hot).collect();
+    let ys: Vec<_> = (0..4).map(|i| one_hot(3 - i)).collect();
+    let (valid_xs, valid_ys) = ([one_hot(0), one_hot(3)], [one_hot(3), one_hot(0)]);
+
+    let mut nn = FeedForward::new([10, 10])
+        .cost(Cost::CrossEntropy)
+        .learn_rate(0.5)
+        .epochs(30)
+        .verbose(false)
+        .build();
+
+    let history = nn.train((&xs, &ys), Some((&valid_xs, &valid_ys))).unwrap();
+    let (first, last) = (history.epochs[0], *history.last().unwrap());
+
+    assert_eq!(history.len(), 30);
+    assert!(last.loss < first.loss);
+    assert_eq!(last.accuracy, 1.0);
+
+    // validation stats are those of the final weights on the held out set
+    let (valid_loss, valid_accuracy) = nn.evaluate((&valid_xs, &valid_ys));
+    assert!((last.valid_loss.unwrap() - valid_loss).abs() < 1e-5);
+    assert_eq!(last.valid_accuracy, Some(valid_accuracy));
+
+    let history = nn.train((&xs, &ys), None).unwrap();
+    assert!(history.epochs.iter().all(|epoch| epoch.valid_loss.is_none() && epoch.valid_accuracy.is_none()));
+}
+
+#[test]
+fn class_weighting() {
+    let labels = [[1.0, 0.0], [1.0, 0.0], [0.0, 1.0], [0.0, 1.0]].map(Mat::from_arr);
+    let xs = [1.0; 4].map(Mat::from_elem);
+
+    let mut params = FeedForward::new([1, 2]);
+    params
+        .cost(Cost::CrossEntropy)
+        .learn_rate(0.5)
+        .epochs(200)
+        .verbose(false);
+
+    let mut plain = params.build();
+    let mut weighted = params.clone().class_weights(&[1.0, 4.0]).build();
+
+    plain.train((&xs, &labels), None).unwrap();
+    weighted.train((&xs, &labels), None).unwrap();
+
+    // conflicting labels settle on the weighted class frequencies
+    let p = plain.predict(&xs[0]);
+    let q = weighted.predict(&xs[0]);
+    assert!((p[(1, 0)] - 0.5).abs() < 0.05);
+    assert!((q[(1, 0)] - 0.8).abs() < 0.05);
+}
+
+#[test]
+#[should_panic(expected = "class_weights must cover every output")]
+fn class_weights_length() {
+    FeedForward::new([1, 3]).class_weights(&[1.0, 2.0]).build();
+}
+
+#[test]
+fn custom_loss() {
+    /// Half squared euclidean distance
+    struct Euclidean;
+
+    impl Loss for Euclidean {
+        fn loss(&self, y: &Mat, a: &Mat) -> f32 {
+            let mut err = Mat::zeros(y.shape());
+            y.sub_to(a, &mut err);
+            0.5 * err.data().iter().map(|n| n * n).sum::<f32>()
+        }
+
+        fn grad(&self, y: &Mat, a: &Mat) -> Mat {
+            let mut err = Mat::zeros(y.shape());
+            a.sub_to(y, &mut err);
+            err
+        }
+    }
+
+    let xs = [0.0, 1.0, 2.0].map(Mat::from_elem);
+    let ys = [1.0, 3.0, 5.0].map(Mat::from_elem);
+
+    let mut nn = FeedForward::new([1, 1])
+        .activation(Act::Lin)
+        .loss(Euclidean)
+        .momentum(0.0)
+        .batch_size(3)
+        .learn_rate(0.1)
+        .epochs(50)
+        .verbose(false)
+        .build();
+
+    let history = nn.train((&xs, &ys), None).unwrap();
+
+    assert!(history.epochs[49].loss < history.epochs[0].loss);
+    assert!(nn.loss((&xs, &ys)) < 0.05);
+}
+
+#[test]
+fn orthogonal_weights() {
+    for (n_in, n_out, gain) in [(3, 5, 1.0), (5, 3, 1.0), (3, 5, 2.0), (5, 3, 2.0)] {
+        let w = Weight::Orthogonal(gain).init(n_in, n_out);
+        let n = n_in.min(n_out);
+        let mut gram = Mat::zeros((n, n));
+
+        if n_out > n_in {
+            w.transposed().mul_to(&w, &mut gram);
+        } else {
+            w.mul_to(&w.transposed(), &mut gram);
+        }
+
+        for i in 0..n {
+            for j in 0..n {
+                let expected = if i == j { gain * gain } else { 0.0 };
+                assert!((gram[(i, j)] - expected).abs() < 1e-4);
+            }
+        }
+    }
+}
+
+#[test]
+fn bias_init() {
+    let n = 10_000;
+
+    assert!(Bias::Value(0.1).init(n).data().iter().all(|b| *b == 0.1));
+
+    let range = Bias::Range(-0.5, 0.25).init(n);
+    assert!(range.data().iter().all(|b| (-0.5..=0.25).contains(b)));
+
+    let normal = Bias::Normal(1.0, 0.5).init(n);
+    let mean = normal.data().iter().sum::<f32>() / n as f32;
+    let var = normal.data().iter().map(|b| (b - mean).powi(2)).sum::<f32>() / n as f32;
+    assert!((mean - 1.0).abs() < 0.05);
+    assert!((var.sqrt() - 0.5).abs() < 0.05);
+
+    let priors = [0.5, 0.3, 0.2];
+    let prior = Bias::Prior(priors.to_vec()).init(3);
+    for (b, p) in prior.data().iter().zip(priors) {
+        assert!((b.exp() - p).abs() < 1e-6);
+    }
+}
+
+#[test]
+fn lsuv_unit_variance() {
+    let xs: Vec<_> = (0..64).map(|_| Mat::random((20, 1), -3.0, 3.0)).collect();
+
+    let mut nn = FeedForward::new([20, 30, 30, 5])
+        .activation(Act::Tanh)
+        .verbose(false)
+        .build();
+
+    nn.lsuv_init(&xs);
+
+    for l in 0..3 {
+        let var = nn.output_variance(l, &xs);
+        assert!((var - 1.0).abs() < 0.1, "layer {} has variance {}", l, var);
+    }
+}
+
+#[test]
+fn adam_regression() {
+    let xs = [0.0, 1.0, 2.0, 3.0].map(Mat::from_elem);
+    let ys = [1.0, 3.0, 5.0, 7.0].map(Mat::from_elem);
+
+    let mut nn = FeedForward::new([1, 1])
+        .activation(Act::Lin)
+        .optimizer(Optim::Adam(0.9, 0.999))
+        .batch_size(4)
+        .learn_rate(0.1)
+        .epochs(300)
+        .verbose(false)
+        .build();
+
+    nn.train((&xs, &ys), None).unwrap();
+
+    assert!(nn.loss((&xs, &ys)) < 0.01);
+}
+
+#[test]
+fn custom_optimizer() {
+    /// Plain gradient descent that counts its steps
+    struct Counted(Arc<AtomicUsize>);
+
+    impl Optimizer for Counted {
+        fn step(&self, state: &mut State, param: &mut Mat, grad: &Mat, eta: f32) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Optim::SGD.step(state, param, grad, eta);
+        }
+    }
+
+    let xs = [0.0, 1.0, 2.0, 3.0].map(Mat::from_elem);
+    let ys = [1.0, 3.0, 5.0, 7.0].map(Mat::from_elem);
+    let steps = Arc::new(AtomicUsize::new(0));
+
+    let mut params = FeedForward::new([1, 1]);
+    params
+        .activation(Act::Lin)
+        .optimizer(Optim::SGD)
+        .weight(Weight::Value(0.0))
+        .batch_size(4)
+        .learn_rate(0.05)
+        .epochs(10)
+        .shuffle(false)
+        .verbose(false);
+
+    let mut sgd = params.build();
+    let mut custom = params.custom_optimizer(Counted(steps.clone())).build();
+
+    sgd.train((&xs, &ys), None).unwrap();
+    custom.train((&xs, &ys), None).unwrap();
+
+    // a weight and a bias step per epoch
+    assert_eq!(steps.load(Ordering::Relaxed), 20);
+    assert_eq!(custom.predict(&xs[3]).data(), sgd.predict(&xs[3]).data());
+}
+
+#[test]
+#[should_panic(expected = "momentum requires a momentum based optimizer")]
+fn momentum_keeps_optimizer() {
+    FeedForward::new([1, 1]).optimizer(Optim::Adam(0.9, 0.999)).momentum(0.9);
+}
+
+#[test]
+fn schedule_rates() {
+    let rate = |schedule, step, steps_per_epoch| Scheduler::new(schedule).rate(1.0, step, steps_per_epoch, 10);
+    let close = |a: f32, b: f32| (a - b).abs() < 1e-5;
+
+    assert_eq!(rate(Schedule::Constant, 57, 10), 1.0);
+
+    let step = Schedule::Step(2, 0.5);
+    assert_eq!([0, 1, 2, 4].map(|epoch| rate(step, epoch, 1)), [1.0, 1.0, 0.5, 0.25]);
+    assert_eq!(rate(step, 25, 10), 0.5);
+
+    assert!(close(rate(Schedule::Exponential(0.9), 30, 10), 0.729));
+
+    // cycles of 2 then 4 epochs, restarting at epochs 2 and 6
+    let cosine = Schedule::CosineRestarts(2, 2.0, 0.0);
+    assert!(close(rate(cosine, 0, 2), 1.0));
+    assert!(close(rate(cosine, 2, 2), 0.5));
+    assert!(close(rate(cosine, 3, 2), 0.5 * (1.0 + (0.75 * std::f32::consts::PI).cos())));
+    assert!(close(rate(cosine, 4, 2), 1.0));
+    assert!(close(rate(cosine, 8, 2), 0.5));
+    assert!(close(rate(cosine, 12, 2), 1.0));
+
+    let warmup = Schedule::Warmup(4);
+    assert_eq!([0, 1, 3, 10].map(|step| rate(warmup, step, 1)), [0.25, 0.5, 1.0, 1.0]);
+
+    // peaks after 30% of the 100 batches, then anneals to the final rate
+    let one_cycle = Schedule::OneCycle(10.0);
+    assert!(close(rate(one_cycle, 0, 10), 1.0));
+    assert!(close(rate(one_cycle, 15, 10), 5.5));
+    assert!(close(rate(one_cycle, 30, 10), 10.0));
+    assert!(close(rate(one_cycle, 100, 10), 1e-3));
+
+    let mut plateau = Scheduler::new(Schedule::Plateau(0.5, 1));
+    for loss in [1.0, 0.5, 0.5, 0.5] {
+        plateau.observe(loss);
+    }
+    assert_eq!(plateau.rate(1.0, 40, 10, 10), 0.5);
+}
+
+#[test]
+#[should_panic(expected = "step must be positive")]
+fn schedule_zero_step() {
+    Scheduler::new(Schedule::Step(0, 0.5));
+}
+
+#[test]
+#[should_panic(expected = "mult must be at least 1")]
+fn schedule_shrinking_restarts() {
+    Scheduler::new(Schedule::CosineRestarts(2, 0.5, 0.0));
+}
+
+#[test]
+fn clipped_regression() {
+    /// Checks every applied parameter step stays within the clip norm
+    struct ClippedSteps {
+        params: (f32, f32)
+    }
+
+    impl Callback<2> for ClippedSteps {
+        fn on_batch_end(&mut self, net: &mut FeedForward<2>, batch: &Batch) -> Control {
+            // predictions at 0 and 1 recover the bias and weight
+            let b = net.predict(&Mat::from_elem(0.0))[(0, 0)];
+            let w = net.predict(&Mat::from_elem(1.0))[(0, 0)] - b;
+            let step = (w - self.params.0).hypot(b - self.params.1);
+
+            // SGD steps are at most η times the clip norm of 1
+            assert!(step <= batch.learn_rate + 1e-4);
+            self.params = (w, b);
+            Control::Continue
+        }
+    }
+
+    let xs = [0.0,  1.0].map(Mat::from_elem);
+    let ys = [10.0, 5.0].map(Mat::from_elem);
+
+    let mut nn = FeedForward::new([1, 1])
+        .activation(Act::Lin)
+        .optimizer(Optim::SGD)
+        .clip(Clip::Norm(1.0))
+        .weight(Weight::Value(0.0))
+        .batch_size(1)
+        .learn_rate(0.5)
+        .epochs(50)
+        .seed(7)
+        .verbose(false)
+        .build();
+
+    nn.add_callback(ClippedSteps { params: (0.0, 0.0) });
+    let history = nn.train((&xs, &ys), None).unwrap();
+
+    assert!(history.epochs.iter().all(|epoch| epoch.loss.is_finite()));
+    // reported norms are measured before clipping
+    assert!(history.epochs[0].grad_norm > 1.0);
+    assert!(nn.loss((&xs, &ys)) < 1.0);
+}
+
+#[test]
+fn weight_decay() {
+    /// Squared norm of a linear layer's weights, read from its
+    /// responses to each basis input
+    fn weight_norm(nn: &mut FeedForward<2>) -> f32 {
+        let bias = nn.predict(&Mat::zeros((3, 1)));
+        (0..3)
+            .map(|i| {
+                let mut x = Mat::zeros((3, 1));
+                x[(i, 0)] = 1.0;
+                let mut column = nn.predict(&x);
+                column.sub_assign(&bias);
+                column.data().iter().map(|n| n * n).sum::<f32>()
+            })
+            .sum()
+    }
+
+    let xs = [[1.0, 0.5, 0.0], [0.0, 1.0, -1.0], [0.5, 0.0, 1.0]].map(Mat::from_arr);
+    let ys = [[2.0, -1.0], [1.0, 3.0], [-2.0, 0.5]].map(Mat::from_arr);
+
+    let train = |optim: Optim, set: &dyn Fn(&mut Params<2>)| {
+        let mut params = FeedForward::new([3, 2]);
+        params
+            .activation(Act::Lin)
+            .optimizer(optim)
+            .weight(Weight::Value(0.0))
+            .batch_size(3)
+            .learn_rate(0.05)
+            .epochs(200)
+            .verbose(false);
+        set(&mut params);
+
+        let mut nn = params.build();
+        nn.train((&xs, &ys), None).unwrap();
+        weight_norm(&mut nn)
+    };
+
+    let plain = train(Optim::SGD, &|_| ());
+    assert!(train(Optim::SGD, &|p| { p.penalty(Penalty::L2(0.1)); }) < 0.9 * plain);
+    assert!(train(Optim::SGD, &|p| { p.penalty(Penalty::L1(0.1)); }) < 0.9 * plain);
+    assert!(train(Optim::SGD, &|p| { p.weight_decay(0.5); }) < 0.9 * plain);
+
+    // AdamW's decay is the same decoupled weight decay
+    let adam = train(Optim::Adam(0.9, 0.999), &|_| ());
+    let adamw = train(Optim::AdamW(0.9, 0.999, 0.5), &|_| ());
+    let decayed = train(Optim::Adam(0.9, 0.999), &|p| { p.weight_decay(0.5); });
+    assert!(adamw < 0.9 * adam);
+    assert!((adamw - decayed).abs() < 1e-5);
+}
+
+#[test]
+fn dropout_eval_mode() {
+    let xs = [0, 1, 2].map(one_hot);
+    let ys = [2, 0, 1].map(one_hot);
+
+    let mut nn = FeedForward::new([10, 32, 10])
+        .cost(Cost::CrossEntropy)
+        .dropout(0, Dropout::Standard(0.5))
+        .batch_size(1)
+        .learn_rate(0.1)
+        .epochs(20)
+        .verbose(false)
+        .build();
+
+    nn.train((&xs, &ys), None).unwrap();
+
+    let a = nn.predict(&xs[0]);
+    let b = nn.predict(&xs[0]);
+
+    assert_eq!(a.data(), b.data());
+    assert_eq!(nn.accuracy((&xs, &ys)), 1.0);
+}
+
+#[test]
+#[should_panic(expected = "dropout rate must be in [0, 1)")]
+fn dropout_full_rate() {
+    FeedForward::new([10, 32, 10]).dropout(0, Dropout::Standard(1.0));
+}
+
+#[test]
+#[should_panic(expected = "dropout rate must be in [0, 1)")]
+fn alpha_dropout_negative_rate() {
+    FeedForward::new([10, 32, 10]).dropout(0, Dropout::Alpha(-0.1));
+}
+
+#[test]
+fn batch_norm() {
+    let xs = [0, 1, 2, 3].map(one_hot);
+    let ys = [3, 2, 1, 0].map(one_hot);
+
+    let mut nn = FeedForward::new([10, 16, 10])
+        .cost(Cost::CrossEntropy)
+        .norm(0, Norm::Batch)
+        .batch_size(4)
+        .learn_rate(0.1)
+        .epochs(100)
+        .verbose(false)
+        .build();
+
+    nn.train((&xs, &ys), None).unwrap();
+
+    assert_eq!(nn.accuracy((&xs, &ys)), 1.0);
+}
+
+#[test]
+fn norm_gradients() {
+    const H: f32 = 1e-2;
+
+    // 3 nodes over a batch of 4 samples, weighted by `c` into a scalar loss
+    let z = Mat::from_fn((3, 4), |(r, c)| (1.3 * r + 0.7 * c * c).sin() + 0.2 * r);
+    let c = Mat::from_fn((3, 4), |(r, c)| (0.9 * r - 1.1 * c).cos());
+
+    let loss = |layer: &mut NormLayer, z: &Mat| {
+        let mut y = z.clone();
+        layer.forward(&mut y, true);
+        y.data().iter().zip(c.data()).map(|(y, c)| y * c).sum::<f32>()
+    };
+
+    for norm in [Norm::Batch, Norm::Layer] {
+        let mut layer = NormLayer::new(norm, 3);
+
+        let mut grad = c.clone();
+        loss(&mut layer, &z);
+        layer.backward(&mut grad);
+
+        for i in 0..z.data().len() {
+            let (mut up, mut down) = (z.clone(), z.clone());
+            up.data_mut()[i] += H;
+            down.data_mut()[i] -= H;
+
+            let numeric = (loss(&mut layer, &up) - loss(&mut layer, &down)) / (2.0 * H);
+            let analytic = grad.data()[i];
+
+            assert!(
+                (numeric - analytic).abs() < 1e-2 * analytic.abs().max(1.0),
+                "dZ[{}] = {} but finite difference is {}", i, analytic, numeric
+            );
+        }
+    }
+}
+
+#[test]
+fn early_stopping() {
+    /// Checks the early stop hooks against the final history
+    #[derive(Default)]
+    struct StopHooks {
+        stopped: Option<usize>,
+        restored: Option<usize>
+    }
+
+    impl Callback<3> for StopHooks {
+        fn on_early_stop(&mut self, _net: &mut FeedForward<3>, epoch: usize) {
+            self.stopped = Some(epoch);
+        }
+
+        fn on_restore_best(&mut self, _net: &mut FeedForward<3>, epoch: usize) {
+            self.restored = Some(epoch);
+        }
+
+        fn on_train_end(&mut self, _net: &mut FeedForward<3>, history: &History) {
+            assert_eq!(self.stopped, Some(history.len() - 1));
+            assert!(self.restored.is_some_and(|epoch| epoch < history.len() - 1));
+        }
+    }
+
+    let xs: Vec<_> = (0..10).map(one_hot).collect();
+    let ys: Vec<_> = (0..10).map(|i| one_hot(9 - i)).collect();
+
+    let mut nn = FeedForward::new([10, 16, 10])
+        .cost(Cost::CrossEntropy)
+        .valid_split(0.2)
+        .early_stop(Metric::Loss, 3, 0.0)
+        .learn_rate(0.5)
+        .epochs(500)
+        .verbose(false)
+        .build();
+
+    nn.add_callback(StopHooks::default());
+    let history = nn.train((&xs, &ys), None).unwrap();
+
+    // held out digits are unlearnable, so validation loss stalls
+    assert!(history.len() < 500);
+    assert!(history.epochs.iter().all(|epoch| epoch.valid_loss.is_some()));
+}
+
+#[test]
+fn callback_stop() {
+    struct StopAt(usize);
+
+    impl Callback<3> for StopAt {
+        fn on_epoch_end(&mut self, _net: &mut FeedForward<3>, epoch: usize, _stats: &Epoch) -> Control {
+            if epoch + 1 == self.0 { Control::Stop } else { Control::Continue }
+        }
+    }
+
+    let xs = [0, 1].map(one_hot);
+    let ys = [1, 0].map(one_hot);
+
+    let mut nn = FeedForward::new([10, 8, 10])
+        .epochs(10)
+        .verbose(false)
+        .build();
+
+    nn.add_callback(StopAt(3));
+
+    assert_eq!(nn.train((&xs, &ys), None).unwrap().len(), 3);
+}
+
+#[test]
+fn history_export() {
+    let xs = [0, 1].map(one_hot);
+    let ys = [1, 0].map(one_hot);
+
+    let mut nn = FeedForward::new([10, 8, 10])
+        .epochs(4)
+        .verbose(false)
+        .build();
+
+    let history = nn.train((&xs, &ys), Some((&xs, &ys))).unwrap();
+    let csv = history.to_csv();
+    let json: History = serde_json::from_str(&history.to_json().unwrap()).unwrap();
+
+    assert_eq!(csv.lines().count(), 5);
+    assert_eq!(json.len(), 4);
+    assert!(history.epochs.iter().all(|epoch| epoch.throughput > 0.0));
+}
+
+#[test]
+fn resume_checkpoint() {
+    let dir = std::env::temp_dir();
+    let model = dir.join("nn-rs-resume-model").to_string_lossy().to_string();
+    let checkpoint = dir.join("nn-rs-resume-checkpoint").to_string_lossy().to_string();
+
+    let xs: Vec<_> = (0..6).map(one_hot).collect();
+    let ys: Vec<_> = (0..6).map(|i| one_hot(5 - i)).collect();
+
+    let mut nn = FeedForward::new([10, 16, 10])
+        .dropout(0, Dropout::Standard(0.2))
+        .batch_size(2)
+        .epochs(4)
+        .seed(7)
+        .save_path(&model)
+        .checkpoint(1, &checkpoint)
+        .verbose(false)
+        .build();
+
+    // identical replica of the untrained model
+    nn.save_model().unwrap();
+    let mut replica = FeedForward::<3>::load_model(&model).unwrap();
+
+    nn.add_callback(Interrupt);
+    assert_eq!(nn.train((&xs, &ys), None).unwrap().len(), 2);
+
+    // callbacks attached before training continues see the remaining epochs
+    let epochs = Arc::new(AtomicUsize::new(0));
+    let mut resumed = FeedForward::<3>::resume(&checkpoint).unwrap();
+    assert_eq!(resumed.epoch(), 2);
+    resumed.add_callback(CountEpochs(epochs.clone()));
+
+    let (mut resumed, history) = resumed.train((&xs, &ys), None).unwrap();
+    let full = replica.train((&xs, &ys), None).unwrap();
+
+    assert_eq!(epochs.load(Ordering::Relaxed), 2);
+    assert_eq!(history.len(), 4);
+    assert_eq!(history.last().unwrap().loss, full.last().unwrap().loss);
+    assert_eq!(resumed.predict(&xs[0]).data(), replica.predict(&xs[0]).data());
+}
+
+/// Stops training after the second epoch
+#[cfg(test)]
+struct Interrupt;
+
+#[cfg(test)]
+impl Callback<3> for Interrupt {
+    fn on_epoch_end(&mut self, _net: &mut FeedForward<3>, epoch: usize, _stats: &Epoch) -> Control {
+        if epoch == 1 { Control::Stop } else { Control::Continue }
+    }
+}
+
+/// Counts the trained epochs
+#[cfg(test)]
+struct CountEpochs(Arc<AtomicUsize>);
+
+#[cfg(test)]
+impl Callback<3> for CountEpochs {
+    fn on_epoch_begin(&mut self, _net: &mut FeedForward<3>, _epoch: usize) -> Control {
+        self.0.fetch_add(1, Ordering::Relaxed);
+        Control::Continue
+    }
+}
+
+#[test]
+#[should_panic(expected = "resume with the sample weights of the interrupted run")]
+fn resume_requires_weights() {
+    let checkpoint = std::env::temp_dir().join("nn-rs-resume-weighted").to_string_lossy().to_string();
+    let xs: Vec<_> = (0..4).map(one_hot).collect();
+    let ys: Vec<_> = (0..4).map(|i| one_hot(3 - i)).collect();
+
+    let mut nn = FeedForward::new([10, 16, 10])
+        .epochs(4)
+        .checkpoint(1, &checkpoint)
+        .verbose(false)
+        .build();
+
+    nn.add_callback(Interrupt);
+    nn.train_weighted((&xs, &ys), &[1.0, 2.0, 1.0, 2.0], None).unwrap();
+
+    let _ = FeedForward::<3>::resume(&checkpoint).unwrap().train((&xs, &ys), None);
+}
+
+#[test]
+#[should_panic(expected = "re-attach the custom loss of the interrupted run")]
+fn resume_requires_loss() {
+    let checkpoint = std::env::temp_dir().join("nn-rs-resume-loss").to_string_lossy().to_string();
+    let xs: Vec<_> = (0..4).map(one_hot).collect();
+    let ys: Vec<_> = (0..4).map(|i| one_hot(3 - i)).collect();
+
+    let mut nn = FeedForward::new([10, 16, 10])
+        .loss(Cost::MAE)
+        .epochs(4)
+        .checkpoint(1, &checkpoint)
+        .verbose(false)
+        .build();
+
+    nn.add_callback(Interrupt);
+    nn.train((&xs, &ys), None).unwrap();
+
+    let _ = FeedForward::<3>::resume(&checkpoint).unwrap().train((&xs, &ys), None);
+}
+
+#[test]
+fn checkpoint_dirs() {
+    let dir = std::env::temp_dir().join("nn-rs-checkpoints");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let xs = [0, 1].map(one_hot);
+    let ys = [1, 0].map(one_hot);
+    let mut params = FeedForward::new([10, 10]);
+    params.epochs(3).verbose(false);
+
+    // missing directories are created
+    let nested = dir.join("nested").join("checkpoint").to_string_lossy().to_string();
+    let history = params.checkpoint(1, &nested).build().train((&xs, &ys), None).unwrap();
+
+    assert_eq!(history.len(), 3);
+    assert!(std::path::Path::new(&nested).is_file());
+
+    // a failed save ends training with the error
+    let blocked = format!("{}/checkpoint", nested);
+    assert!(params.checkpoint(1, &blocked).build().train((&xs, &ys), None).is_err());
+}
+
+#[test]
+fn legacy_model() {
+    // saved before optimizers, initializers, normalization and dropout were configurable
+    let json = r#"{
+        "params": {
+            "form": [2, 2, 1], "learn_rate": 0.01, "momentum": 0.8, "batch_size": 32, "epochs": 5,
+            "weight": {"Range": [-0.2, 0.2]}, "act": "Tanh", "cost": "MSE",
+            "shuffle": true, "verbose": false, "save_path": "src/models/model"
+        },
+        "layers": [
+            {
+                "weights": {"buf": [0.5, 0.0, -0.5, 0.0], "row": 2, "col": 2},
+                "w_grad": {"buf": [0.0, 0.0, 0.0, 0.0], "row": 2, "col": 2},
+                "w_grad_acc": {"buf": [0.0, 0.0, 0.0, 0.0], "row": 2, "col": 2},
+                "w_momentum": {"buf": [0.0, 0.0, 0.0, 0.0], "row": 2, "col": 2},
+                "biases": {"buf": [0.0, 0.0], "row": 2, "col": 1},
+                "grad": {"buf": [0.0, 0.0], "row": 2, "col": 1},
+                "grad_acc": {"buf": [0.0, 0.0], "row": 2, "col": 1},
+                "sums": {"buf": [0.0, 0.0], "row": 2, "col": 1},
+                "act": "Tanh"
+            },
+            {
+                "weights": {"buf": [1.0, 2.0], "row": 1, "col": 2},
+                "w_grad": {"buf": [0.0, 0.0], "row": 1, "col": 2},
+                "w_grad_acc": {"buf": [0.0, 0.0], "row": 1, "col": 2},
+                "w_momentum": {"buf": [0.0, 0.0], "row": 1, "col": 2},
+                "biases": {"buf": [0.0], "row": 1, "col": 1},
+                "grad": {"buf": [0.0], "row": 1, "col": 1},
+                "grad_acc": {"buf": [0.0], "row": 1, "col": 1},
+                "sums": {"buf": [0.0], "row": 1, "col": 1},
+                "act": "Tanh"
+            }
+        ],
+        "acts": [
+            {"buf": [0.0, 0.0], "row": 2, "col": 1},
+            {"buf": [0.0, 0.0], "row": 2, "col": 1},
+            {"buf": [0.0], "row": 1, "col": 1}
+        ]
+    }"#;
+
+    let path = std::env::temp_dir().join("nn-rs-legacy").to_string_lossy().to_string();
+    std::fs::write(&path, json).unwrap();
+
+    let mut nn = FeedForward::<3>::load_model(&path).unwrap();
+    let x = Mat::from_arr([1.0, 0.0]);
+    let expected = (0.5f32.tanh() + 2.0 * (-0.5f32).tanh()).tanh();
+
+    assert!((nn.predict(&x)[(0, 0)] - expected).abs() < 1e-6);
+
+    // missing optimizer state is sized on the first step
+    let history = nn.train((&[x], &[Mat::from_elem(0.5)]), None).unwrap();
+    assert!(history.epochs.iter().all(|epoch| epoch.loss.is_finite()));
+}
+
+#[test]
+fn parallel_training() {
+    let xs: Vec<_> = (0..6).map(one_hot).collect();
+    let ys: Vec<_> = (0..6).map(|i| one_hot(5 - i)).collect();
+
+    let train = |threads| {
+        let mut nn = FeedForward::new([10, 10])
+            .cost(Cost::CrossEntropy)
+            .weight(Weight::Value(0.0))
+            .shuffle(false)
+            .batch_size(6)
+            .threads(threads)
+            .learn_rate(0.5)
+            .epochs(20)
+            .verbose(false)
+            .build();
+
+        nn.train((&xs, &ys), None).unwrap();
+        (nn.loss((&xs, &ys)), nn.accuracy((&xs, &ys)))
+    };
+
+    let (loss, accuracy) = train(1);
+    let (parallel_loss, parallel_accuracy) = train(4);
+
+    assert!((loss - parallel_loss).abs() < 1e-4);
+    assert_eq!(accuracy, parallel_accuracy);
+}
+
+#[test]
+fn lr_range_test() {
+    let xs: Vec<_> = (0..6).map(one_hot).collect();
+    let ys: Vec<_> = (0..6).map(|i| one_hot(5 - i)).collect();
+
+    let mut params = FeedForward::new([10, 16, 10]);
+    params
+        .cost(Cost::CrossEntropy)
+        .weight(Weight::Value(0.1))
+        .batch_size(2)
+        .seed(3)
+        .verbose(false);
+
+    let mut nn = params.build();
+
+    let before = nn.predict(&xs[0]);
+    let curve = nn.find_lr((&xs, &ys), 1e-4, 10.0, 60);
+    let rate = curve.suggestion().unwrap();
+
+    assert_eq!(nn.predict(&xs[0]).data(), before.data());
+    assert!((1e-4..=10.0).contains(&rate));
+
+    // the range test leaves the seeded random stream untouched
+    let untested = params.build().train((&xs, &ys), None).unwrap();
+    let tested = nn.train((&xs, &ys), None).unwrap();
+    assert_eq!(tested.last().unwrap().loss, untested.last().unwrap().loss);
+}
+
+#[test]
+fn successive_halving() {
+    let xs: Vec<_> = (0..10).map(one_hot).collect();
+    let ys: Vec<_> = (0..10).map(|i| one_hot(i % 2)).collect();
+    let path = std::env::temp_dir().join("nn-rs-tuner/leaderboard.json").to_string_lossy().to_string();
+    let _ = std::fs::remove_dir_all(std::env::temp_dir().join("nn-rs-tuner"));
+
+    let base = FeedForward::new([10, 8, 10])
+        .cost(Cost::CrossEntropy)
+        .epochs(8)
+        .seed(3)
+        .verbose(false)
+        .clone();
+
+    let space = Space::new()
+        .learn_rate(&[0.01, 0.1, 0.5])
+        .momentum(&[0.5, 0.9])
+        .widths(&[&[4], &[16]])
+        .clone();
+
+    let board = Tuner::new(&base, space, Search::Halving(6, 2))
+        .save_path(&path)
+        .run((&xs, &ys))
+        .unwrap();
+
+    let saved: Leaderboard = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+
+    assert_eq!(board.trials.len(), 6);
+    assert_eq!(saved.trials.len(), 6);
+    assert_eq!(board.best().unwrap().epochs, 8);
+
+    // scores are only ranked against trials of the same budget
+    assert!(board.trials.windows(2).all(|w| {
+        w[0].epochs > w[1].epochs || (w[0].epochs == w[1].epochs && w[0].score <= w[1].score)
+    }));
+}
+
+#[test]
+fn tuner_shared_split() {
+    let xs: Vec<_> = (0..10).map(one_hot).collect();
+    let ys: Vec<_> = (0..10).map(|i| one_hot(i % 2)).collect();
+    let path = std::env::temp_dir().join("nn-rs-tuner-split/leaderboard.json").to_string_lossy().to_string();
+
+    // unseeded, but otherwise deterministic training
+    let base = FeedForward::new([10, 8, 10])
+        .weight(Weight::Value(0.1))
+        .shuffle(false)
+        .epochs(4)
+        .verbose(false)
+        .clone();
+
+    let space = Space::new().learn_rate(&[0.1; 4]).clone();
+    let board = Tuner::new(&base, space, Search::Grid)
+        .save_path(&path)
+        .run((&xs, &ys))
+        .unwrap();
+
+    // identical candidates score identically on the shared split
+    let best = board.best().unwrap().score;
+    assert!(board.trials.iter().all(|trial| trial.score == best));
+}
+
+#[test]
+#[should_panic(expected = "momentum is only searched for momentum based optimizers")]
+fn tuner_momentum_optimizer() {
+    let base = FeedForward::new([10, 8, 10]).optimizer(Optim::Adam(0.9, 0.999)).clone();
+    Tuner::new(&base, Space::new().momentum(&[0.9]).clone(), Search::Grid);
+}
+
+#[test]
+fn stratified_k_fold() {
+    let xs: Vec<_> = (0..9).map(one_hot).collect();
+    let ys: Vec<_> = (0..9).map(|i| one_hot(i % 3)).collect();
+    let root = std::env::temp_dir().join("nn-rs-k-fold");
+    let dir = root.join("models").to_string_lossy().to_string();
+    let _ = std::fs::remove_dir_all(&root);
+
+    let params = FeedForward::new([10, 8, 10])
+        .cost(Cost::CrossEntropy)
+        .learn_rate(0.1)
+        .epochs(10)
+        .verbose(false)
+        .clone();
+
+    let cv = KFold::new(3)
+        .stratified(true)
+        .save_models(&dir)
+        .run(&params, (&xs, &ys), Metric::Accuracy)
+        .unwrap();
+
+    assert_eq!(cv.folds.len(), 3);
+    assert!((0.0..=1.0).contains(&cv.mean));
+    assert!(cv.std >= 0.0);
+    assert!(FeedForward::<3>::load_model(&format!("{}/fold_2", dir)).is_ok());
+
+    // every fold holds out one sample of each class
+    for fold in cv.folds.iter() {
+        let mut labels: Vec<_> = fold.held_out.iter().map(|&i| ys[i].max_index().0).collect();
+        labels.sort();
+        assert_eq!(labels, [0, 1, 2]);
+    }
+}
+
+#[test]
+fn weight_averaging() {
+    let xs = [0, 1, 2, 3].map(one_hot);
+    let ys = [3, 2, 1, 0].map(one_hot);
+
+    let mut nn = FeedForward::new([10, 16, 10])
+        .cost(Cost::CrossEntropy)
+        .norm(0, Norm::Batch)
+        .ema(0.9)
+        .swa(60)
+        .batch_size(4)
+        .learn_rate(0.1)
+        .epochs(100)
+        .verbose(false)
+        .build();
+
+    nn.train((&xs, &ys), None).unwrap();
+    let trained = nn.predict(&xs[0]);
+
+    assert!(nn.swap_ema());
+    assert_eq!(nn.accuracy((&xs, &ys)), 1.0);
+    assert!(nn.swap_ema());
+    assert_eq!(nn.predict(&xs[0]).data(), trained.data());
+
+    assert!(nn.swap_swa(&xs));
+    assert_eq!(nn.accuracy((&xs, &ys)), 1.0);
+
+    // swapping back restores the trained batch norm statistics
+    assert!(nn.swap_swa(&xs));
+    assert_eq!(nn.predict(&xs[0]).data(), trained.data());
+}
+
+#[test]
+fn distillation() {
+    let xs: Vec<_> = (0..6).map(one_hot).collect();
+    let ys: Vec<_> = (0..6).map(|i| one_hot(5 - i)).collect();
+
+    let test_xs: Vec<_> = xs.iter().map(|x| x.scale(0.9)).collect();
+
+    // the draw app's default MSE and tanh setup
+    let mut teacher = FeedForward::new([10, 32, 16, 10])
+        .learn_rate(0.1)
+        .epochs(100)
+        .verbose(false)
+        .build();
+
+    teacher.train((&xs, &ys), None).unwrap();
+
+    let student = FeedForward::new([10, 8, 10])
+        .learn_rate(0.1)
+        .epochs(100)
+        .verbose(false)
+        .clone();
+
+    let distilled = distill(&mut teacher, &student, (&xs, &ys), (&test_xs, &ys), 2.0, 0.5).unwrap();
+
+    assert_eq!(distilled.teacher_accuracy, 1.0);
+    assert_eq!(distilled.accuracy, 1.0);
+    assert_eq!(distilled.history.len(), 100);
+
+    // negative outputs still soften to a distribution
+    let soft = Distillation::soften(&Mat::from_arr([-3.0, -1.0, -2.0]), 2.0);
+    let sum: f32 = soft.data().iter().sum();
+
+    assert!(soft.data().iter().all(|p| p.is_finite() && *p > 0.0));
+    assert!((sum - 1.0).abs() < 1e-5);
+    assert_eq!(soft.max_index(), (1, 0));
+}
+
+#[test]
+fn magnitude_pruning() {
+    let xs: Vec<_> = (0..6).map(one_hot).collect();
+    let ys: Vec<_> = (0..6).map(|i| one_hot(5 - i)).collect();
+    let path = std::env::temp_dir().join("nn-rs-sparse").to_string_lossy().to_string();
+    let dense_path = std::env::temp_dir().join("nn-rs-dense").to_string_lossy().to_string();
+
+    let mut nn = FeedForward::new([10, 32, 10])
+        .cost(Cost::CrossEntropy)
+        .ema(0.9)
+        .learn_rate(0.1)
+        .epochs(100)
+        .save_path(&dense_path)
+        .verbose(false)
+        .build();
+
+    nn.train((&xs, &ys), None).unwrap();
+
+    let steps = nn.prune(0.8, Prune::Gradual(Scope::Global, 4, 10), (&xs, &ys));
+    let last = steps.last().unwrap();
+
+    assert_eq!(steps.len(), 4);
+    assert!((last.sparsity - 0.8).abs() < 0.01);
+
+    // the averages are pruned along with the weights
+    assert!(nn.swap_ema());
+    assert!(nn.sparsity() >= last.sparsity);
+    assert!(nn.swap_ema());
+
+    // pruned weights stay zero while training
+    nn.train((&xs, &ys), None).unwrap();
+    assert!(nn.sparsity() >= last.sparsity);
+
+    nn.save_sparse(&path).unwrap();
+    nn.save_model().unwrap();
+    let mut loaded = FeedForward::<3>::load_sparse(&path).unwrap();
+
+    assert_eq!(loaded.predict(&xs[0]).data(), nn.predict(&xs[0]).data());
+    assert_eq!(loaded.accuracy((&xs, &ys)), nn.accuracy((&xs, &ys)));
+
+    // only the nonzero weights are stored
+    let size = |path: &str| std::fs::metadata(path).unwrap().len();
+    assert!(size(&path) < size(&dense_path) / 2);
+}
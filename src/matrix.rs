@@ -1,9 +1,9 @@
-use std::ops::{Index, IndexMut};
+use std::{ops::{Index, IndexMut}, f32::consts::PI};
 use rand::{distributions::Uniform, prelude::Distribution};
 use serde::{Serialize, Deserialize};
 use matrixmultiply::sgemm;
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct Mat {
     buf: Vec<f32>,
     row: usize,
@@ -198,6 +198,26 @@ impl Mat {
         }
     }
 
+    /// Stacks column vectors `cols` into a matrix
+    pub fn from_cols<'a, I>(cols: I) -> Self 
+    where 
+        I: IntoIterator<Item=&'a Mat> 
+    {
+        let cols: Vec<_> = cols.into_iter().collect();
+        let row = cols[0].row();
+        let col = cols.len();
+
+        let buf = (0..row*col)
+            .map(|i| cols[i % col].buf[i / col])
+            .collect();
+
+        Self {
+            buf,
+            row,
+            col
+        }
+    }
+
     pub fn zeros((row, col): (usize, usize)) -> Self {
         Self {
             buf: vec![0.0; row*col],
@@ -229,6 +249,34 @@ impl Mat {
         }
     }
 
+    /// Samples a matrix from the normal distribution `N(mean, std²)`
+    /// 
+    /// ## Note
+    /// Uses the Box-Muller transform
+    pub fn random_normal((row, col): (usize, usize), mean: f32, std: f32) -> Self {
+        let uniform = Uniform::from(f32::EPSILON..1.0);
+        let mut rng = rand::thread_rng();
+
+        let buf = (0..row*col)
+            .map(|_| {
+                let u1: f32 = uniform.sample(&mut rng);
+                let u2: f32 = uniform.sample(&mut rng);
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * PI * u2).cos();
+                mean + std * z
+            })
+            .collect();
+
+        Self {
+            buf,
+            row,
+            col
+        }
+    }
+
+    pub fn data_mut(&mut self) -> &mut [f32] {
+        &mut self.buf
+    }
+
     pub fn transposed(&self) -> Transpose<'_> {
         Transpose { 
             view: self, 
@@ -251,6 +299,15 @@ impl Mat {
         }
     }
 
+    /// Adds column vector `rhs` to every column
+    pub fn add_col_assign(&mut self, rhs: &Mat) {
+        assert_eq!((self.row(), 1), rhs.shape());
+
+        for (i, n) in self.buf.iter_mut().enumerate() {
+            *n += rhs.buf[i / self.col];
+        }
+    }
+
     pub fn scale_assign(&mut self, scalar: f32) {
         for n in self.buf.iter_mut() {
             *n *= scalar;
@@ -295,6 +352,32 @@ impl Mat {
         Mat::from_vec(self.shape(), buf)
     }
 
+    pub fn column(&self, col: usize) -> Mat {
+        let buf = (0..self.row)
+            .map(|row| self[(row, col)])
+            .collect();
+
+        Mat::from_vec((self.row, 1), buf)
+    }
+
+    pub fn set_column(&mut self, col: usize, rhs: &Mat) {
+        assert_eq!((self.row(), 1), rhs.shape());
+
+        for row in 0..self.row {
+            self[(row, col)] = rhs.buf[row];
+        }
+    }
+
+    /// Sums the columns into a column vector
+    pub fn sum_cols(&self) -> Mat {
+        let buf = self.buf
+            .chunks(self.col)
+            .map(|row| row.iter().sum())
+            .collect();
+
+        Mat::from_vec((self.row, 1), buf)
+    }
+
     pub fn fill(&mut self, value: f32) {
         for n in self.buf.iter_mut() {
             *n = value;
@@ -1,41 +1,84 @@
-use rand::seq::SliceRandom;
+use rand::{seq::SliceRandom, RngCore};
 use serde::{Serialize, Deserialize};
 use std::{time::Instant, fs::File, io::Error};
 use crate::matrix::{Mat, MatBase};
 use crate::{
     activation::Act, 
+    callback::{Batch, Callback, Control, Logger},
+    clip::Clip,
+    dropout::Dropout,
+    history::{Epoch, History},
+    lr_find::LrCurve,
     parameters::Params, 
     back_index::Side::Rev,
-    cost::Cost,
+    loss::Loss,
+    norm::{Norm, NormLayer, NormParams},
+    optimizer::{Optim, Optimizer, State},
+    penalty::Penalty,
+    prune::{magnitude_masks, Csr, Prune, PruneStep},
+    rng::Pcg,
+    schedule::Scheduler,
+    stopping::Stopper,
+    weight_init::Weight,
 };
 
+/// Tolerated distance from unit variance during LSUV initialization
+const LSUV_TOLERANCE: f32 = 0.1;
+/// Maximum rescaling iterations per layer during LSUV initialization
+const LSUV_MAX_ITERS: usize = 10;
+/// Loss smoothing of the learning rate range test
+const LR_FIND_SMOOTHING: f32 = 0.98;
+/// Loss growth over the best loss that ends the learning rate range test
+const LR_FIND_DIVERGENCE: f32 = 4.0;
+
 /// Represents a neuron layer
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 struct Layer {
     weights:    Mat,
     w_grad:     Mat,
     w_grad_acc: Mat,
-    w_momentum: Mat,
+    #[serde(default)]
+    w_state:    State,
     biases:     Mat,
     grad:       Mat,
     grad_acc:   Mat,
+    #[serde(default)]
+    b_state:    State,
     sums:       Mat,
-    act:        Act
+    #[serde(default)]
+    mask:       Mat,
+    #[serde(default)]
+    dropout:    Option<Dropout>,
+    #[serde(default)]
+    norm:       Option<NormLayer>,
+    act:        Act,
+    /// Whether zeroed weights are held at zero
+    #[serde(default)]
+    pruned:     bool,
+    /// Mask of the unpruned weights, rebuilt from the zeroed weights once loaded
+    #[serde(skip)]
+    keep:       Option<Mat>
 }
 
 impl Layer {
-    /// Creates `Layer` given nodes going `n_in` and `n_out`
-    pub fn new<const L: usize>(params: &Params<L>, n_in: usize, n_out: usize) -> Self {
+    /// Creates the `l`th `Layer` given nodes going `n_in` and `n_out`
+    pub fn new<const L: usize>(params: &Params<L>, l: usize, n_in: usize, n_out: usize) -> Self {
         Self {
-            weights:    params.weight.init(n_in, n_out),
+            weights:    params.weight_for(l).init(n_in, n_out),
             w_grad:     Mat::zeros((n_out, n_in)),
             w_grad_acc: Mat::zeros((n_out, n_in)),
-            w_momentum: Mat::zeros((n_out, n_in)),
-            biases:     Mat::zeros((n_out, 1)),
+            w_state:    State::new((n_out, n_in)),
+            biases:     params.bias_for(l).init(n_out),
             grad:       Mat::zeros((n_out, 1)),
             grad_acc:   Mat::zeros((n_out, 1)),
+            b_state:    State::new((n_out, 1)),
             sums:       Mat::zeros((n_out, 1)),
-            act: params.act
+            mask:       Mat::filled((n_out, 1), 1.0),
+            dropout:    params.dropout[l],
+            norm:       params.norm[l].map(|norm| NormLayer::new(norm, n_out)),
+            act: params.act,
+            pruned:     false,
+            keep:       None
         }
     }
 
@@ -44,12 +87,39 @@ impl Layer {
     /// 
     /// ## Equations
     /// - `Z ₗ = W ₗ x A ₗ + B ₗ`
+    /// - `Z ₗ = norm( W ₗ x A ₗ + B ₗ )` with normalization
     /// - `A ₗ₊₁ = σ( Zₗ )`
+    /// - `A ₗ₊₁ = σ( Zₗ ) . M ₗ + S ₗ` when `training` with dropout
+    /// 
+    /// ## Note
+    /// `a` holds one sample per column
     #[inline]
-    fn forward_pass(&mut self, a: &Mat) -> Mat {
+    fn forward_pass(&mut self, a: &Mat, training: bool, rng: &mut Pcg) -> Mat {
+        let shape = (self.weights.row(), a.col());
+
+        // resize propagation data to the batch
+        if self.sums.shape() != shape {
+            self.sums = Mat::zeros(shape);
+            self.grad = Mat::zeros(shape);
+        }
+
         self.weights.mul_to(a, &mut self.sums);
-        self.sums.add_assign(&self.biases);
-        self.sums.map(|n| self.act.value(n))
+        self.sums.add_col_assign(&self.biases);
+
+        if let Some(norm) = &mut self.norm {
+            norm.forward(&mut self.sums, training);
+        }
+
+        let mut a_next = self.sums.map(|n| self.act.value(n));
+
+        if let (Some(dropout), true) = (self.dropout, training) {
+            let (mask, shift) = dropout.mask(a_next.shape(), rng);
+            a_next.elem_mul_assign(&mask);
+            a_next.add_assign(&shift);
+            self.mask = mask;
+        }
+
+        a_next
     }
 
     /// Computes a backward pass from `l ← l1`
@@ -57,10 +127,25 @@ impl Layer {
     /// 
     /// ## Equations
     /// - `ϵ ₗ₋₁ = W ₗᵀ x ϵ ₗ . σ'( Z ₗ₋₁ )`
+    /// - `ϵ ₗ₋₁ = W ₗᵀ x ϵ ₗ . σ'( Z ₗ₋₁ ) . M ₗ₋₁` with dropout
     #[inline]
     fn backward_pass(&self, l_prev: &mut Layer) {
         self.weights.transposed().mul_to(&self.grad, &mut l_prev.grad);
         l_prev.grad.elem_mul_assign(&l_prev.sums.map(|n| l_prev.act.deriv(n)));
+
+        if l_prev.dropout.is_some() {
+            l_prev.grad.elem_mul_assign(&l_prev.mask);
+        }
+
+        l_prev.norm_err();
+    }
+
+    /// Backpropagates error through the normalization, if any
+    #[inline]
+    fn norm_err(&mut self) {
+        if let Some(norm) = &mut self.norm {
+            norm.backward(&mut self.grad);
+        }
     }
 
     /// Computes weight error on layer `l`
@@ -72,34 +157,139 @@ impl Layer {
         self.grad.mul_to(&a_prev.transposed(), &mut self.w_grad);
     }
 
-    /// Computes error on output layer `L`
+    /// Computes error on output layer `L`, 
+    /// scaling the error of each sample by its `weights`
     /// 
     /// ## Equations
-    /// - `ϵ ₗ = cost'( y - A ₗ ) . σ'( Z ₗ )`
+    /// - `ϵ ₗ = -∇loss( A ₗ, y ) . σ'( Z ₗ )`
+    /// - `ϵ ₗ = -∇loss( A ₗ, y )` for fused losses
     #[inline]
-    fn output_err(&mut self, cost: Cost, y: &Mat, a_out: &Mat) {
-        y.sub_to(a_out, &mut self.grad);
-        self.grad.map_assign(|n| *n = cost.deriv(*n));
-        self.grad.elem_mul_assign(&self.sums.map(|n| self.act.deriv(n)));
+    fn output_err(&mut self, loss: &dyn Loss, y: &Mat, a_out: &Mat, weights: &[f32]) {
+        for (j, weight) in weights.iter().enumerate() {
+            let mut grad = loss.grad(&y.column(j), &a_out.column(j));
+            grad.scale_assign(-weight);
+            self.grad.set_column(j, &grad);
+        }
+
+        // fused losses already account for the output non-linearity
+        if !loss.is_fused() {
+            self.grad.elem_mul_assign(&self.sums.map(|n| self.act.deriv(n)));
+        }
+
+        self.norm_err();
     }
 
     /// Accumulate error
     #[inline]
     fn accum_err(&mut self) {
-        self.grad_acc.add_assign(&self.grad);
+        self.grad_acc.add_assign(&self.grad.sum_cols());
         self.w_grad_acc.add_assign(&self.w_grad);
+
+        if let Some(norm) = &mut self.norm {
+            norm.accum_err();
+        }
+    }
+
+    /// Average error accumulated over `samples`
+    #[inline]
+    fn average_err(&mut self, samples: usize) {
+        self.w_grad_acc.scale_assign(1.0 / samples as f32);
+        self.grad_acc.scale_assign(1.0 / samples as f32);
+
+        if let Some(norm) = &mut self.norm {
+            norm.average_err(samples);
+        }
+    }
+
+    /// Penalize accumulated error, excluding biases unless `biases`
+    #[inline]
+    fn penalize_err(&mut self, penalty: Penalty, biases: bool) {
+        let w_penalty = self.weights.map(|n| penalty.deriv(n));
+        self.w_grad_acc.sub_assign(&w_penalty);
+
+        if biases {
+            let b_penalty = self.biases.map(|n| penalty.deriv(n));
+            self.grad_acc.sub_assign(&b_penalty);
+        }
+    }
+
+    /// Penalty of the layer parameters, excluding biases unless `biases`
+    #[inline]
+    fn penalty(&self, penalty: Penalty, biases: bool) -> f32 {
+        let mut value = penalty.value(&self.weights);
+
+        if biases {
+            value += penalty.value(&self.biases);
+        }
+
+        value
+    }
+
+    /// Squared L2 norm of accumulated error
+    #[inline]
+    fn err_norm_sq(&self) -> f32 {
+        let norm_sq: f32 = self.w_grad_acc.data()
+            .iter()
+            .chain(self.grad_acc.data())
+            .map(|n| n * n)
+            .sum();
+
+        match &self.norm {
+            Some(norm) => norm_sq + norm.err_norm_sq(),
+            None => norm_sq
+        }
+    }
+
+    /// Clip accumulated error given the global error `norm`
+    #[inline]
+    fn clip_err(&mut self, clip: Clip, norm: f32) {
+        clip.apply(&mut self.w_grad_acc, norm);
+        clip.apply(&mut self.grad_acc, norm);
+
+        if let Some(layer_norm) = &mut self.norm {
+            layer_norm.clip_err(clip, norm);
+        }
+    }
+
+    /// Apply accumulated error with decoupled weight `decay`
+    /// 
+    /// ## Equations
+    /// - `W ₗ -= η λ W ₗ`
+    #[inline]
+    fn apply_err(&mut self, optim: &dyn Optimizer, eta: f32, decay: f32) {
+        if self.pruned && self.keep.is_none() {
+            self.keep = Some(self.weights.map(|n| if n == 0.0 { 0.0 } else { 1.0 }));
+        }
+
+        if decay > 0.0 {
+            self.weights.scale_assign(1.0 - eta * decay);
+        }
+
+        optim.step(&mut self.w_state, &mut self.weights, &self.w_grad_acc, eta);
+        optim.step(&mut self.b_state, &mut self.biases, &self.grad_acc, eta);
+
+        // pruned weights stay zero
+        if let Some(keep) = &self.keep {
+            self.weights.elem_mul_assign(keep);
+        }
+
+        if let Some(norm) = &mut self.norm {
+            norm.apply_err(optim, eta);
+        }
     }
 
-    /// Apply accumulated error
+    /// Rescales the incoming weights of every node to an L2 norm of at most `max`
     #[inline]
-    fn apply_err(&mut self, momentum: f32, eta: f32) {
-        self.w_momentum.scale_assign(momentum);
-        self.w_momentum.add_assign(&self.w_grad_acc.scale(eta));
+    fn constrain_norm(&mut self, max: f32) {
+        let n_in = self.weights.col();
 
-        self.weights.add_assign(&self.w_grad_acc.scale(eta));
-        self.weights.add_assign(&self.w_momentum);
+        for row in self.weights.data_mut().chunks_mut(n_in) {
+            let norm = row.iter().map(|n| n * n).sum::<f32>().sqrt();
 
-        self.biases.add_assign(&self.grad_acc.scale(eta));
+            if norm > max {
+                row.iter_mut().for_each(|n| *n *= max / norm);
+            }
+        }
     }
 
     /// Clears propagation data
@@ -110,24 +300,266 @@ impl Layer {
         self.w_grad.fill(0.0);
     }
 
+    /// Copies the trainable parameters of `other`
+    fn sync_params(&mut self, other: &Layer) {
+        self.weights.data_mut().copy_from_slice(other.weights.data());
+        self.biases.data_mut().copy_from_slice(other.biases.data());
+        self.norm.clone_from(&other.norm);
+    }
+
+    /// Adds the error accumulated by `other`, clearing it
+    fn merge_err(&mut self, other: &mut Layer) {
+        self.w_grad_acc.add_assign(&other.w_grad_acc);
+        self.grad_acc.add_assign(&other.grad_acc);
+
+        if let (Some(norm), Some(other)) = (&mut self.norm, &mut other.norm) {
+            norm.merge_err(other);
+        }
+
+        other.clear_accum();
+    }
+
+    /// Zeroes the weights outside of mask `keep`, holding them at zero
+    fn prune(&mut self, keep: Mat) {
+        self.weights.elem_mul_assign(&keep);
+        self.keep = Some(keep);
+        self.pruned = true;
+    }
+
     /// Clears accumulation data
     #[inline]
     fn clear_accum(&mut self) {
         self.w_grad_acc.fill(0.0);
         self.grad_acc.fill(0.0);
+
+        if let Some(norm) = &mut self.norm {
+            norm.clear_accum();
+        }
     }
 }
 
+/// Propagation mode of a network
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Mode {
+    /// Applies dropout
+    Train,
+    /// Propagates deterministically
+    #[default]
+    Eval
+}
+
 /// Neural Network
 #[derive(Serialize, Deserialize)]
 pub struct FeedForward<const L: usize> {
     params: Params<L>,
     layers: Vec<Layer>,
-    acts:   Vec<Mat>
+    acts:   Vec<Mat>,
+    /// Random stream of shuffling and dropout
+    #[serde(default)]
+    rng:    Pcg,
+    #[serde(default)]
+    averages: Averages,
+    #[serde(skip)]
+    mode:   Mode,
+    #[serde(skip)]
+    callbacks: Vec<Box<dyn Callback<L>>>
+}
+
+/// Inference state of a model with sparse weights
+#[derive(Serialize, Deserialize)]
+struct SparseModel<const L: usize> {
+    params: Params<L>,
+    layers: Vec<SparseLayer>
+}
+
+#[derive(Serialize, Deserialize)]
+struct SparseLayer {
+    weights: Csr,
+    biases:  Mat,
+    norm:    Option<NormLayer>
+}
+
+/// Trainable parameters of a `Layer`
+#[derive(Serialize, Deserialize, Clone)]
+struct LayerParams {
+    weights: Mat,
+    biases:  Mat,
+    norm:    Option<NormParams>
+}
+
+impl LayerParams {
+    /// Copies the parameters of `layer`
+    fn of(layer: &Layer) -> Self {
+        Self {
+            weights: layer.weights.clone(),
+            biases:  layer.biases.clone(),
+            norm:    layer.norm.as_ref().map(NormLayer::params)
+        }
+    }
+
+    /// Moves the parameters a fraction `t` of the way towards those of `layer`
+    fn blend(&mut self, layer: &Layer, t: f32) {
+        for (a, b) in [(&mut self.weights, &layer.weights), (&mut self.biases, &layer.biases)] {
+            a.data_mut()
+                .iter_mut()
+                .zip(b.data())
+                .for_each(|(a, b)| *a += t * (b - *a));
+        }
+
+        if let (Some(norm), Some(other)) = (&mut self.norm, &layer.norm) {
+            norm.blend(other, t);
+        }
+    }
+
+    /// Exchanges the parameters with those of `layer`
+    fn swap(&mut self, layer: &mut Layer) {
+        std::mem::swap(&mut self.weights, &mut layer.weights);
+        std::mem::swap(&mut self.biases, &mut layer.biases);
+
+        if let (Some(norm), Some(other)) = (&mut self.norm, &mut layer.norm) {
+            other.swap_params(norm);
+        }
+    }
+}
+
+/// Running averages of the model parameters
+#[derive(Serialize, Deserialize, Clone, Default)]
+struct Averages {
+    /// Exponential moving average, updated every batch
+    ema: Option<Vec<LayerParams>>,
+    /// Stochastic weight average and its averaged epoch count
+    swa: Option<(Vec<LayerParams>, usize)>,
+    /// Whether the model holds the stochastic weight average
+    #[serde(default)]
+    swa_swapped: bool
+}
+
+/// Training progress saved with checkpoints
+#[derive(Serialize, Deserialize)]
+struct TrainState {
+    /// Next epoch to train
+    epoch: usize,
+    /// Applied batches
+    step: usize,
+    /// Shuffled order of the training samples
+    indices: Vec<usize>,
+    /// Samples held out for validation
+    held_out: Vec<usize>,
+    scheduler: Scheduler,
+    stopper: Option<Stopper>,
+    /// Layers of the best epoch watched by `stopper`
+    best_layers: Option<Vec<Layer>>,
+    history: History,
+    /// Whether samples are weighted
+    #[serde(default)]
+    weighted: bool,
+    /// Whether a custom loss overrides the cost
+    #[serde(default)]
+    custom_loss: bool,
+    /// Whether a custom optimizer overrides the optimizer
+    #[serde(default)]
+    custom_optim: bool
+}
+
+/// Model and training state of an interrupted run
+#[derive(Deserialize)]
+struct Checkpoint<const L: usize> {
+    net: FeedForward<L>,
+    state: TrainState
+}
+
+#[derive(Serialize)]
+struct CheckpointRef<'a, const L: usize> {
+    net: &'a FeedForward<L>,
+    state: &'a TrainState
+}
+
+/// An interrupted run restored from a checkpoint, paused before its next epoch
+/// 
+/// ## Note
+/// Callbacks, custom losses and custom optimizers aren't checkpointed,
+/// so they're re-attached here before training continues
+pub struct Resumed<const L: usize> {
+    pub net: FeedForward<L>,
+    state: TrainState
+}
+
+impl<const L: usize> Resumed<L> {
+    /// Returns the next epoch to train
+    pub fn epoch(&self) -> usize {
+        self.state.epoch
+    }
+
+    /// Attaches a training `callback`
+    pub fn add_callback<C: Callback<L> + 'static>(&mut self, callback: C) -> &mut Self {
+        self.net.add_callback(callback);
+        self
+    }
+
+    /// Re-attaches the custom `loss` of the interrupted run
+    pub fn loss<T: Loss + 'static>(&mut self, loss: T) -> &mut Self {
+        self.net.params.loss(loss);
+        self
+    }
+
+    /// Re-attaches the custom `optimizer` of the interrupted run
+    pub fn custom_optimizer<T: Optimizer + 'static>(&mut self, optimizer: T) -> &mut Self {
+        self.net.params.custom_optimizer(optimizer);
+        self
+    }
+
+    /// Continues training on inputs `xs` and labels `ys`
+    /// 
+    /// ## Note
+    /// `data` and `valid` must be those of the interrupted run
+    pub fn train(
+        self, 
+        data: (&[Mat], &[Mat]), 
+        valid: Option<(&[Mat], &[Mat])>
+    ) -> Result<(FeedForward<L>, History), Error> {
+        self.fit(data, None, valid)
+    }
+
+    /// Continues training on inputs `xs` and labels `ys`
+    /// with the sample `weights` of the interrupted run
+    /// 
+    /// ## Note
+    /// `data` and `valid` must be those of the interrupted run
+    pub fn train_weighted(
+        self, 
+        data: (&[Mat], &[Mat]), 
+        weights: &[f32], 
+        valid: Option<(&[Mat], &[Mat])>
+    ) -> Result<(FeedForward<L>, History), Error> {
+        self.fit(data, Some(weights), valid)
+    }
+
+    fn fit(
+        self, 
+        data: (&[Mat], &[Mat]), 
+        weights: Option<&[f32]>, 
+        valid: Option<(&[Mat], &[Mat])>
+    ) -> Result<(FeedForward<L>, History), Error> {
+        let Self { mut net, state } = self;
+
+        assert_eq!(state.weighted, weights.is_some(), "resume with the sample weights of the interrupted run");
+        assert!(!state.custom_loss || net.params.loss.is_some(), "re-attach the custom loss of the interrupted run");
+        assert!(!state.custom_optim || net.params.custom_optim.is_some(), "re-attach the custom optimizer of the interrupted run");
+
+        let history = net.fit(data, weights, valid, Some(state))?;
+        Ok((net, history))
+    }
 }
 
 impl<const L: usize> From<Params<L>> for FeedForward<L> {
     fn from(params: Params<L>) -> Self {
+        if let Some(class_weights) = &params.class_weights {
+            assert_eq!(class_weights.len(), params.form[L-1], "class_weights must cover every output");
+        }
+        if let Optim::AdamW(..) = params.optim {
+            assert_eq!(params.weight_decay, 0.0, "AdamW already sets the weight decay");
+        }
+
         let acts = params.form
             .iter()
             .map(|l| Mat::zeros((*l, 1)))
@@ -135,19 +567,25 @@ impl<const L: usize> From<Params<L>> for FeedForward<L> {
 
         let layers = params.form
             .windows(2)
-            .map(|l| Layer::new(&params, l[0], l[1]))
+            .enumerate()
+            .map(|(i, l)| Layer::new(&params, i, l[0], l[1]))
             .collect();
 
         Self {
             acts,
             layers,
-            params
+            rng: params.seed.map_or_else(Pcg::from_entropy, Pcg::new),
+            params,
+            averages: Averages::default(),
+            mode: Mode::Eval,
+            callbacks: Vec::new()
         }
     }
 }
 
 impl<const L: usize> FeedForward<L> {
     /// Creates a new `Net` builder
+    #[allow(clippy::new_ret_no_self)]
     pub fn new(form: [usize; L]) -> Params<L> {
         Params::from(form)
     }
@@ -163,32 +601,414 @@ impl<const L: usize> FeedForward<L> {
 
     /// Reads a model from `path`
     pub fn load_model(path: &str) -> Result<Self, Error> {
-        let path = std::env::current_dir()?.join(&path);
+        let path = std::env::current_dir()?.join(path);
         let src = std::fs::read_to_string(&path)?;
         serde_json::from_str(&src).map_err(|err| err.into())
     }
 
+    /// Saves the inference state of the model to `path`,
+    /// storing only the nonzero weights
+    /// 
+    /// ## Note
+    /// Gradients and optimizer state aren't saved
+    pub fn save_sparse(&self, path: &str) -> Result<(), Error> {
+        let layers = self.layers
+            .iter()
+            .map(|layer| SparseLayer {
+                weights: Csr::from_dense(&layer.weights),
+                biases: layer.biases.clone(),
+                norm: layer.norm.clone()
+            })
+            .collect();
+
+        let json = serde_json::to_string(&SparseModel { params: self.params.clone(), layers })?;
+        let path = std::env::current_dir()?.join(path);
+        std::fs::write(&path, json)
+    }
+
+    /// Reads a model saved by `save_sparse` from `path`
+    /// 
+    /// ## Note
+    /// Zeroed weights stay pruned during further training
+    pub fn load_sparse(path: &str) -> Result<Self, Error> {
+        let path = std::env::current_dir()?.join(path);
+        let src = std::fs::read_to_string(&path)?;
+        let model: SparseModel<L> = serde_json::from_str(&src)?;
+        let mut net = Self::from(model.params);
+
+        for (layer, sparse) in net.layers.iter_mut().zip(model.layers) {
+            layer.weights = sparse.weights.to_dense();
+            layer.biases = sparse.biases;
+            layer.norm = sparse.norm;
+            layer.pruned = true;
+        }
+
+        Ok(net)
+    }
+
+    /// Returns the fraction of zeroed weights
+    pub fn sparsity(&self) -> f32 {
+        let (zeros, total) = self.layers
+            .iter()
+            .map(|layer| layer.weights.data())
+            .fold((0, 0), |(zeros, total), w| {
+                (zeros + w.iter().filter(|&&n| n == 0.0).count(), total + w.len())
+            });
+
+        zeros as f32 / total as f32
+    }
+
+    /// Prunes the smallest-magnitude weights to `sparsity` following `schedule`,
+    /// measuring every round on inputs `xs` and labels `ys`
+    /// 
+    /// ## Note
+    /// - Gradual schedules fine-tune on `xs` and `ys` between rounds
+    /// - Pruned weights stay zero during further training,
+    ///   and are zeroed in the tracked weight averages
+    pub fn prune(&mut self, sparsity: f32, schedule: Prune, data: (&[Mat], &[Mat])) -> Vec<PruneStep> {
+        assert!((0.0..1.0).contains(&sparsity));
+        let mut steps = Vec::new();
+
+        for target in schedule.rounds(sparsity) {
+            let weights: Vec<_> = self.layers.iter().map(|layer| &layer.weights).collect();
+            let masks = magnitude_masks(&weights, target, schedule.scope());
+
+            let Averages { ema, swa, .. } = &mut self.averages;
+            for avg in ema.iter_mut().chain(swa.iter_mut().map(|(swa, _)| swa)) {
+                for (params, keep) in avg.iter_mut().zip(&masks) {
+                    params.weights.elem_mul_assign(keep);
+                }
+            }
+
+            for (layer, keep) in self.layers.iter_mut().zip(masks) {
+                layer.prune(keep);
+            }
+
+            if let Prune::Gradual(_, _, epochs) = schedule {
+                self.fine_tune(data, epochs);
+            }
+
+            let (loss, accuracy) = self.evaluate(data);
+            let step = PruneStep { sparsity: self.sparsity(), loss, accuracy };
+
+            if self.params.verbose {
+                println!("sparsity {}: loss {} accuracy {}", step.sparsity, step.loss, step.accuracy);
+            }
+
+            steps.push(step);
+        }
+
+        steps
+    }
+
+    /// Retrains on inputs `xs` and labels `ys` for `epochs` at the base learn rate
+    /// 
+    /// ## Note
+    /// Unlike `train`, no callbacks, checkpoints, early stopping or averages are run
+    fn fine_tune(&mut self, (xs, ys): (&[Mat], &[Mat]), epochs: usize) {
+        let mut indices: Vec<_> = (0..xs.len()).collect();
+
+        for _ in 0..epochs {
+            if self.params.shuffle {
+                indices.shuffle(&mut self.rng);
+            }
+
+            for batch in indices.chunks(self.params.batch_size) {
+                self.accum_batch(batch, (xs, ys), None);
+                self.apply_batch(batch.len(), self.params.learn_rate);
+            }
+        }
+    }
+
+    /// Returns the model parameters
+    pub fn params(&self) -> &Params<L> {
+        &self.params
+    }
+
+    /// Registers a `callback` invoked during training
+    pub fn add_callback<C: Callback<L> + 'static>(&mut self, callback: C) {
+        self.callbacks.push(Box::new(callback));
+    }
+
+    /// Returns the current propagation `mode`
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Set the propagation `mode` of `forward_pass`
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    /// Layer-sequential unit-variance initialization over sample batch `xs`
+    /// 
+    /// Orthogonally initializes each layer, then rescales its weights
+    /// until the layer outputs `Z ₗ` have unit variance over `xs`
+    pub fn lsuv_init(&mut self, xs: &[Mat]) {
+        assert!(!xs.is_empty());
+        self.mode = Mode::Eval;
+
+        for l in 0..L-1 {
+            let (n_out, n_in) = self.layers[l].weights.shape();
+            self.layers[l].weights = Weight::Orthogonal(1.0).init(n_in, n_out);
+
+            for _ in 0..LSUV_MAX_ITERS {
+                let var = self.output_variance(l, xs);
+
+                if var <= 0.0 || (var - 1.0).abs() < LSUV_TOLERANCE {
+                    break
+                }
+
+                self.layers[l].weights.scale_assign(1.0 / var.sqrt());
+            }
+        }
+    }
+
+    /// Measures the variance of the outputs `Z ₗ` of layer `l` over `xs`
+    /// 
+    /// ## Note
+    /// Switches the model to `Mode::Eval`
+    pub fn output_variance(&mut self, l: usize, xs: &[Mat]) -> f32 {
+        self.mode = Mode::Eval;
+
+        let mut sum = 0.0;
+        let mut sum_sq = 0.0;
+        let mut count = 0;
+
+        for x in xs {
+            self.forward_pass(x);
+
+            for z in self.layers[l].sums.data() {
+                sum += z;
+                sum_sq += z * z;
+                count += 1;
+            }
+        }
+
+        let mean = sum / count as f32;
+        sum_sq / count as f32 - mean * mean
+    }
+
     /// Forward propagates and returns a model prediction
+    /// 
+    /// ## Note
+    /// Switches the model to `Mode::Eval`
     pub fn predict(&mut self, x: &Mat) -> Mat {
+        self.mode = Mode::Eval;
         self.forward_pass(x);
         self.acts[Rev(0)].clone()
     }
 
+    /// Forward propagates and returns the output pre-activation `Z ₗ`
+    /// 
+    /// ## Note
+    /// Switches the model to `Mode::Eval`
+    pub fn logits(&mut self, x: &Mat) -> Mat {
+        self.mode = Mode::Eval;
+        self.forward_pass(x);
+        self.layers[Rev(0)].sums.clone()
+    }
+
     /// Trains the model on inputs `xs` and labels `ys`
-    pub fn train(&mut self, (xs, ys): (&[Mat], &[Mat])) {
+    /// 
+    /// Reports the loss on the optional `valid` set after every epoch,
+    /// holding out `valid_split` of the data when no set is given
+    /// 
+    /// ## Note
+    /// Training ends early with an error if a checkpoint can't be saved
+    pub fn train(&mut self, data: (&[Mat], &[Mat]), valid: Option<(&[Mat], &[Mat])>) -> Result<History, Error> {
+        self.fit(data, None, valid, None)
+    }
+
+    /// Trains the model on inputs `xs` and labels `ys`, 
+    /// scaling each sample's contribution by `weights`
+    /// 
+    /// Reports the loss on the optional `valid` set after every epoch,
+    /// holding out `valid_split` of the data when no set is given
+    /// 
+    /// ## Note
+    /// Training ends early with an error if a checkpoint can't be saved
+    pub fn train_weighted(
+        &mut self, 
+        data: (&[Mat], &[Mat]), 
+        weights: &[f32], 
+        valid: Option<(&[Mat], &[Mat])>
+    ) -> Result<History, Error> {
+        self.fit(data, Some(weights), valid, None)
+    }
+
+    /// Learning rate range test over inputs `xs` and labels `ys`
+    /// 
+    /// Trains a copy of the model for `steps` batches with a rate increasing
+    /// exponentially from `min` to `max`, recording the smoothed loss
+    /// 
+    /// ## Note
+    /// - The test ends early once the loss diverges
+    /// - The model's own parameters are left untouched
+    pub fn find_lr(&self, (xs, ys): (&[Mat], &[Mat]), min: f32, max: f32, steps: usize) -> LrCurve {
         assert_eq!(xs.len(), ys.len());
+        assert!(0.0 < min && min < max && steps > 1);
 
-        // index map for shuffling the immutable data
+        // seeded from a copy so the model's own random stream doesn't advance
+        let mut net = self.replica(self.rng.clone().next_u64());
         let mut indices: Vec<_> = (0..xs.len()).collect();
+        let mut curve = LrCurve::default();
 
-        for epoch in 0..self.params.epochs {
-            let timer = Instant::now();
+        let batch_size = self.params.batch_size;
+        let batches = xs.len().div_ceil(batch_size);
+        let mut smoothed = 0.0;
+        let mut best = f32::INFINITY;
 
-            if self.params.verbose {
-                println!("epoch {} of {}", epoch+1, self.params.epochs);
+        for step in 0..steps {
+            let k = step % batches;
+            if k == 0 && self.params.shuffle {
+                indices.shuffle(&mut net.rng);
+            }
+
+            let batch = &indices[k*batch_size..((k+1)*batch_size).min(xs.len())];
+            let eta = min * (max / min).powf(step as f32 / (steps - 1) as f32);
+
+            let (loss, _, total) = net.accum_batch(batch, (xs, ys), None);
+            net.apply_batch(batch.len(), eta);
+
+            // debiased exponential moving average
+            smoothed = LR_FIND_SMOOTHING * smoothed + (1.0 - LR_FIND_SMOOTHING) * loss / total;
+            let loss = smoothed / (1.0 - LR_FIND_SMOOTHING.powi(step as i32 + 1));
+
+            if !loss.is_finite() || loss > LR_FIND_DIVERGENCE * best {
+                break
+            }
+
+            best = best.min(loss);
+            curve.rates.push(eta);
+            curve.losses.push(loss);
+        }
+
+        curve
+    }
+
+    /// Reads the interrupted run checkpointed at `path`,
+    /// to continue training once its callbacks are attached
+    pub fn resume(path: &str) -> Result<Resumed<L>, Error> {
+        let path = std::env::current_dir()?.join(path);
+        let src = std::fs::read_to_string(&path)?;
+        let Checkpoint { net, state } = serde_json::from_str(&src)?;
+        Ok(Resumed { net, state })
+    }
+
+    /// Saves the current model and training `state` to `checkpoint_path`,
+    /// creating its directory
+    fn save_checkpoint(&self, state: &TrainState) -> Result<(), Error> {
+        let json = serde_json::to_string(&CheckpointRef { net: self, state })?;
+        let path = std::env::current_dir()?.join(&self.params.checkpoint_path);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, json)
+    }
+
+    /// Creates the initial `TrainState` over `n` samples, holding out
+    /// a validation split unless `explicit_valid` is given
+    fn train_state(&mut self, n: usize, explicit_valid: bool, weighted: bool) -> TrainState {
+        // index map for shuffling the immutable data
+        let mut indices: Vec<_> = (0..n).collect();
+        let mut held_out = Vec::new();
+
+        if !explicit_valid && self.params.valid_split > 0.0 {
+            indices.shuffle(&mut self.rng);
+
+            let n_valid = (n as f32 * self.params.valid_split).round() as usize;
+            held_out = indices.split_off(n - n_valid);
+        }
+
+        TrainState {
+            epoch: 0,
+            step: 0,
+            indices,
+            held_out,
+            scheduler: Scheduler::new(self.params.schedule),
+            stopper: self.params.early_stop.map(Stopper::new),
+            best_layers: None,
+            history: History::default(),
+            weighted,
+            custom_loss: self.params.loss.is_some(),
+            custom_optim: self.params.custom_optim.is_some()
+        }
+    }
+
+    /// Trains the model with optional per-sample `weights`,
+    /// continuing from `state` when resuming a checkpoint
+    /// 
+    /// ## Note
+    /// - With `early_stop` set, training halts once the watched metric
+    ///   stops improving and the best-scoring weights are restored
+    /// - Any callback may halt training by returning `Control::Stop`
+    /// - With `checkpoint_every` set, the model and training state
+    ///   are saved to `checkpoint_path` every that many epochs,
+    ///   and training stops at the first failed save
+    fn fit(
+        &mut self, 
+        (xs, ys): (&[Mat], &[Mat]), 
+        weights: Option<&[f32]>, 
+        valid: Option<(&[Mat], &[Mat])>,
+        state: Option<TrainState>
+    ) -> Result<History, Error> {
+        assert_eq!(xs.len(), ys.len());
+        if let Some(weights) = weights {
+            assert_eq!(xs.len(), weights.len());
+        }
+
+        let mut state = state.unwrap_or_else(|| self.train_state(xs.len(), valid.is_some(), weights.is_some()));
+
+        // validate on the held out split unless given an explicit set
+        let held_out = (!state.held_out.is_empty()).then(|| {
+            let valid_xs: Vec<_> = state.held_out.iter().map(|&i| xs[i].clone()).collect();
+            let valid_ys: Vec<_> = state.held_out.iter().map(|&i| ys[i].clone()).collect();
+            (valid_xs, valid_ys)
+        });
+        let valid = valid.or(held_out
+            .as_ref()
+            .map(|(xs, ys)| (xs.as_slice(), ys.as_slice())));
+
+        let steps_per_epoch = state.indices.len().div_ceil(self.params.batch_size);
+        let mut eta = self.params.learn_rate;
+
+        // batch normalization needs the whole batch on one thread
+        let batched = self.params.norm.contains(&Some(Norm::Batch));
+        let threads = if batched { 1 } else { self.params.threads };
+        let mut workers = Vec::new();
+
+        // callbacks are detached so they can borrow the model
+        let mut callbacks = std::mem::take(&mut self.callbacks);
+        if self.params.verbose {
+            callbacks.insert(0, Box::new(Logger::default()));
+        }
+
+        for callback in callbacks.iter_mut() {
+            callback.on_train_begin(self);
+        }
+
+        let mut failed = None;
+
+        while state.epoch < self.params.epochs {
+            let epoch = state.epoch;
+            let mut stop = false;
+
+            for callback in callbacks.iter_mut() {
+                stop |= callback.on_epoch_begin(self, epoch) == Control::Stop;
+            }
+            if stop {
+                break
             }
+
+            let timer = Instant::now();
+
             if self.params.shuffle {
-                indices.shuffle(&mut rand::thread_rng());
+                state.indices.shuffle(&mut self.rng);
+            }
+            if threads > 1 {
+                let seeds: Vec<_> = (0..threads).map(|_| self.rng.next_u64()).collect();
+                workers = seeds.into_iter().map(|seed| self.replica(seed)).collect();
             }
 
             // clear accumulated gradients
@@ -196,60 +1016,408 @@ impl<const L: usize> FeedForward<L> {
                 layer.clear_accum();
             }
 
-            let mut samples = 0;
+            let mut loss = 0.0;
+            let mut accurate = 0.0;
+            let mut total = 0.0;
+            let mut grad_norm = 0.0;
+            let mut batches = 0;
+            let mut trained = 0;
+
+            for (index, batch) in state.indices.chunks(self.params.batch_size).enumerate() {
+                // evaluate and accumulate gradients
+                let (batch_loss, batch_accurate, batch_total) = if workers.is_empty() {
+                    self.accum_batch(batch, (xs, ys), weights)
+                } else {
+                    self.accum_parallel(&mut workers, batch, (xs, ys), weights)
+                };
+
+                // scheduled learn rate
+                eta = state.scheduler.rate(self.params.learn_rate, state.step, steps_per_epoch, self.params.epochs);
+
+                let batch_norm = self.apply_batch(batch.len(), eta);
+
+                if let Some(decay) = self.params.ema {
+                    self.update_ema(decay);
+                }
+
+                loss += batch_loss;
+                accurate += batch_accurate;
+                total += batch_total;
+                grad_norm += batch_norm;
+                batches += 1;
+                trained += batch.len();
+                state.step += 1;
+
+                let batch = Batch {
+                    index,
+                    loss: batch_loss / batch_total,
+                    accuracy: batch_accurate / batch_total,
+                    learn_rate: eta,
+                    grad_norm: batch_norm
+                };
+
+                for callback in callbacks.iter_mut() {
+                    stop |= callback.on_batch_end(self, &batch) == Control::Stop;
+                }
+                if stop {
+                    break
+                }
+            }
+
+            let train_secs = timer.elapsed().as_secs_f32();
 
-            for i in 0..xs.len() {
-                // evaluate gradients
-                self.backward_pass(&xs[i], &ys[i]);
-                
-                // accumulate evaluated gradients
-                for layer in self.layers.iter_mut() {
-                    layer.accum_err();
+            let (valid_loss, valid_accuracy) = match valid {
+                Some(valid) => {
+                    let (loss, accuracy) = self.evaluate(valid);
+                    (Some(loss), Some(accuracy))
                 }
+                None => (None, None)
+            };
 
-                samples += 1;
+            let stats = Epoch {
+                loss: loss / total + self.penalty(),
+                accuracy: accurate / total,
+                valid_loss,
+                valid_accuracy,
+                learn_rate: eta,
+                grad_norm: grad_norm / batches as f32,
+                elapsed: timer.elapsed().as_secs_f32(),
+                throughput: trained as f32 / train_secs
+            };
 
-                if samples == self.params.batch_size || i + 1 == xs.len() {
-                    // learn rate
-                    let eta = self.params.learn_rate / samples as f32;
-                    
-                    // apply accumulated gradients
-                    for layer in self.layers.iter_mut() {
-                        layer.apply_err(self.params.momentum, eta);
-                        layer.clear_accum();
+            state.scheduler.observe(valid_loss.unwrap_or(stats.loss));
+            state.history.epochs.push(stats);
+
+            if self.params.swa.is_some_and(|start| epoch >= start) {
+                self.update_swa();
+            }
+
+            let mut stop_early = false;
+            if let Some(stopper) = state.stopper.as_mut() {
+                if stopper.observe(epoch, &stats) {
+                    state.best_layers = Some(self.layers.clone());
+                }
+                stop_early = stopper.should_stop();
+            }
+
+            for callback in callbacks.iter_mut() {
+                stop |= callback.on_epoch_end(self, epoch, &stats) == Control::Stop;
+            }
+
+            if stop_early {
+                for callback in callbacks.iter_mut() {
+                    callback.on_early_stop(self, epoch);
+                }
+                stop = true;
+            }
+
+            state.epoch += 1;
+
+            if let Some(every) = self.params.checkpoint_every {
+                // `is_multiple_of` would raise the minimum toolchain
+                #[allow(clippy::manual_is_multiple_of)]
+                let due = state.epoch % every == 0;
+
+                if due {
+                    if let Err(err) = self.save_checkpoint(&state) {
+                        failed = Some(err);
+                        stop = true;
                     }
+                }
+            }
+
+            if stop {
+                break
+            }
+        }
+
+        // restore the best-scoring weights
+        if let (Some(stopper), Some(layers)) = (&state.stopper, state.best_layers.take()) {
+            for callback in callbacks.iter_mut() {
+                callback.on_restore_best(self, stopper.best_epoch());
+            }
+            self.layers = layers;
+        }
+
+        for callback in callbacks.iter_mut() {
+            callback.on_train_end(self, &state.history);
+        }
+
+        if self.params.verbose {
+            callbacks.remove(0);
+        }
+        self.callbacks = callbacks;
+
+        match failed {
+            Some(err) => Err(err),
+            None => Ok(state.history)
+        }
+    }
+
+    /// Evaluates and accumulates the gradients of `samples`,
+    /// returning their weighted `(loss, accurate_predictions, total_weight)`
+    fn accum_batch(
+        &mut self, 
+        samples: &[usize], 
+        (xs, ys): (&[Mat], &[Mat]), 
+        weights: Option<&[f32]>
+    ) -> (f32, f32, f32) {
+        // batch normalization needs the whole batch propagated at once
+        let chunk = if self.params.norm.contains(&Some(Norm::Batch)) { samples.len() } else { 1 };
+
+        let mut loss = 0.0;
+        let mut accurate = 0.0;
+        let mut total = 0.0;
+
+        for samples in samples.chunks(chunk) {
+            let x = Mat::from_cols(samples.iter().map(|&i| &xs[i]));
+            let y = Mat::from_cols(samples.iter().map(|&i| &ys[i]));
+            let sample_weights: Vec<_> = samples
+                .iter()
+                .map(|&i| self.sample_weight(&ys[i], weights.map_or(1.0, |w| w[i])))
+                .collect();
+
+            // evaluate gradients
+            self.backward(&x, &y, &sample_weights);
 
-                    samples = 0;
+            // measure the propagated prediction
+            let (sample_loss, sample_accurate) = self.measure(&y, &sample_weights);
+            loss += sample_loss;
+            accurate += sample_accurate;
+            total += sample_weights.iter().sum::<f32>();
+
+            // accumulate evaluated gradients
+            for layer in self.layers.iter_mut() {
+                layer.accum_err();
+            }
+        }
+
+        (loss, accurate, total)
+    }
+
+    /// Splits `samples` across `workers`, reducing their 
+    /// accumulated gradients into `self`
+    /// 
+    /// ## Note
+    /// Matches `accum_batch` up to float reassociation
+    fn accum_parallel(
+        &mut self, 
+        workers: &mut [Self], 
+        samples: &[usize], 
+        data: (&[Mat], &[Mat]), 
+        weights: Option<&[f32]>
+    ) -> (f32, f32, f32) {
+        for worker in workers.iter_mut() {
+            for (layer, master) in worker.layers.iter_mut().zip(&self.layers) {
+                layer.sync_params(master);
+            }
+        }
+
+        let part = samples.len().div_ceil(workers.len());
+        let stats: Vec<_> = std::thread::scope(|scope| {
+            let handles: Vec<_> = workers
+                .iter_mut()
+                .zip(samples.chunks(part))
+                .map(|(worker, samples)| scope.spawn(move || worker.accum_batch(samples, data, weights)))
+                .collect();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("training worker panicked"))
+                .collect()
+        });
+
+        for worker in workers.iter_mut() {
+            for (layer, replica) in self.layers.iter_mut().zip(worker.layers.iter_mut()) {
+                layer.merge_err(replica);
+            }
+        }
+
+        stats
+            .into_iter()
+            .fold((0.0, 0.0, 0.0), |acc, n| (acc.0 + n.0, acc.1 + n.1, acc.2 + n.2))
+    }
+
+    /// Folds the current parameters into their exponential moving average
+    /// 
+    /// ## Equations
+    /// - `θ̄ = decay θ̄ + (1 - decay) θ`
+    fn update_ema(&mut self, decay: f32) {
+        match &mut self.averages.ema {
+            Some(ema) => {
+                for (avg, layer) in ema.iter_mut().zip(&self.layers) {
+                    avg.blend(layer, 1.0 - decay);
                 }
             }
+            None => self.averages.ema = Some(self.layers.iter().map(LayerParams::of).collect())
+        }
+    }
 
-            if self.params.verbose {
-                let elapsed = timer.elapsed();
-                println!("finished in {}s", elapsed.as_secs());
-                let accuracy = self.accuracy((xs, ys));
-                println!("accuracy: {}", accuracy);
+    /// Folds the current parameters into their stochastic weight average
+    /// 
+    /// ## Equations
+    /// - `θ̄ = (n θ̄ + θ) / (n + 1)`
+    fn update_swa(&mut self) {
+        match &mut self.averages.swa {
+            Some((swa, n)) => {
+                for (avg, layer) in swa.iter_mut().zip(&self.layers) {
+                    avg.blend(layer, 1.0 / (*n + 1) as f32);
+                }
+                *n += 1;
             }
+            None => self.averages.swa = Some((self.layers.iter().map(LayerParams::of).collect(), 1))
         }
     }
 
-    /// Forward propagates input `x`
+    /// Swaps the model parameters with their exponential moving average,
+    /// returning whether `ema` was tracked
     /// 
     /// ## Note
-    /// `Self` caches the propagated activations
+    /// Swapping again restores the trained parameters
+    pub fn swap_ema(&mut self) -> bool {
+        let Some(ema) = &mut self.averages.ema else {
+            return false
+        };
+
+        for (avg, layer) in ema.iter_mut().zip(self.layers.iter_mut()) {
+            avg.swap(layer);
+        }
+
+        true
+    }
+
+    /// Swaps the model parameters with their stochastic weight average,
+    /// returning whether `swa` was tracked
+    /// 
+    /// ## Note
+    /// - Batch normalization statistics of the average are recomputed over inputs `xs`
+    /// - Swapping again restores the trained parameters and statistics
+    pub fn swap_swa(&mut self, xs: &[Mat]) -> bool {
+        let Some((swa, _)) = &mut self.averages.swa else {
+            return false
+        };
+
+        for (avg, layer) in swa.iter_mut().zip(self.layers.iter_mut()) {
+            avg.swap(layer);
+        }
+        self.averages.swa_swapped = !self.averages.swa_swapped;
+
+        // averaged weights need statistics of their own
+        if self.averages.swa_swapped && self.params.norm.contains(&Some(Norm::Batch)) {
+            self.refresh_norm(xs);
+        }
+
+        true
+    }
+
+    /// Recomputes the batch normalization statistics over inputs `xs`
+    pub fn refresh_norm(&mut self, xs: &[Mat]) {
+        for norm in self.layers.iter_mut().filter_map(|layer| layer.norm.as_mut()) {
+            norm.begin_refresh();
+        }
+
+        self.mode = Mode::Train;
+        for batch in xs.chunks(self.params.batch_size) {
+            self.forward_pass(&Mat::from_cols(batch));
+        }
+        self.mode = Mode::Eval;
+
+        for norm in self.layers.iter_mut().filter_map(|layer| layer.norm.as_mut()) {
+            norm.end_refresh();
+        }
+    }
+
+    /// Creates a training replica of `self` with its own
+    /// propagation buffers and a random stream from `seed`
+    fn replica(&self, seed: u64) -> Self {
+        Self {
+            params: self.params.clone(),
+            layers: self.layers.clone(),
+            acts: self.acts.clone(),
+            rng: Pcg::new(seed),
+            averages: Averages::default(),
+            mode: Mode::Train,
+            callbacks: Vec::new()
+        }
+    }
+
+    /// Applies the gradients accumulated over `samples` with learn rate `eta`,
+    /// returning their pre-clip global norm
+    fn apply_batch(&mut self, samples: usize, eta: f32) -> f32 {
+        // average and penalize accumulated gradients
+        for layer in self.layers.iter_mut() {
+            layer.average_err(samples);
+
+            if let Some(penalty) = self.params.penalty {
+                layer.penalize_err(penalty, self.params.penalize_biases);
+            }
+        }
+
+        // global gradient norm
+        let norm = self.layers
+            .iter()
+            .map(|layer| layer.err_norm_sq())
+            .sum::<f32>()
+            .sqrt();
+
+        // apply accumulated gradients
+        for layer in self.layers.iter_mut() {
+            if let Some(clip) = self.params.clip {
+                layer.clip_err(clip, norm);
+            }
+
+            layer.apply_err(self.params.optim_fn(), eta, self.params.decay());
+            layer.clear_accum();
+
+            if let Some(max) = self.params.max_norm {
+                layer.constrain_norm(max);
+            }
+        }
+
+        norm
+    }
+
+    /// Forward propagates input `x` in the current `mode`
+    /// 
+    /// ## Note
+    /// - `x` holds one sample per column
+    /// - `Self` caches the propagated activations
     pub fn forward_pass(&mut self, x: &Mat) {
         self.acts[0] = x.clone();
+        let training = self.mode == Mode::Train;
 
         for l in 0..L-1 {
             // forward propagate layer activations
-            self.acts[l+1] = self.layers[l].forward_pass(&self.acts[l]);
+            self.acts[l+1] = self.layers[l].forward_pass(&self.acts[l], training, &mut self.rng);
+        }
+
+        // fused losses replace the output non-linearity
+        let loss = self.params.loss_fn();
+        if loss.is_fused() {
+            let sums = &self.layers[Rev(0)].sums;
+
+            for j in 0..sums.col() {
+                if let Some(a_out) = loss.link(&sums.column(j)) {
+                    self.acts[Rev(0)].set_column(j, &a_out);
+                }
+            }
         }
     }
 
     /// Backward propagates input `x` against label `y`
     /// 
     /// ## Note
-    /// `Self` caches the propagated error 
+    /// - `x` and `y` hold one sample per column
+    /// - `Self` caches the propagated error and switches to `Mode::Train`
     pub fn backward_pass(&mut self, x: &Mat, y: &Mat) {
+        self.backward(x, y, &vec![1.0; x.col()]);
+    }
+
+    /// Backward propagates input `x` against label `y`,
+    /// scaling the error of each sample by its `weights`
+    fn backward(&mut self, x: &Mat, y: &Mat, weights: &[f32]) {
+        self.mode = Mode::Train;
+
         for layer in self.layers.iter_mut() {
             layer.clear_prop();
         }
@@ -257,7 +1425,7 @@ impl<const L: usize> FeedForward<L> {
         // propagate input
         self.forward_pass(x);
         // evaluate output error
-        self.layers[Rev(0)].output_err(self.params.cost, y, &self.acts[Rev(0)]);
+        self.layers[Rev(0)].output_err(self.params.loss_fn(), y, &self.acts[Rev(0)], weights);
 
         for l in 0..L-1 {
             // evaluate layer weight error
@@ -271,6 +1439,88 @@ impl<const L: usize> FeedForward<L> {
         }
     }
 
+    /// Measures the mean loss over inputs `xs` and labels `ys`
+    /// 
+    /// ## Note
+    /// Samples are weighted by the model's `class_weights`,
+    /// and the weight `penalty` is included
+    pub fn loss(&mut self, data: (&[Mat], &[Mat])) -> f32 {
+        self.evaluate(data).0
+    }
+
+    /// Measures the class-weighted `(mean_loss, accuracy)` 
+    /// over inputs `xs` and labels `ys`
+    pub fn evaluate(&mut self, (xs, ys): (&[Mat], &[Mat])) -> (f32, f32) {
+        assert_eq!(xs.len(), ys.len());
+
+        let mut loss = 0.0;
+        let mut accurate = 0.0;
+        let mut total = 0.0;
+
+        self.mode = Mode::Eval;
+
+        for (x, y) in xs.iter().zip(ys.iter()) {
+            let weight = self.sample_weight(y, 1.0);
+
+            self.forward_pass(x);
+
+            let (sample_loss, sample_accurate) = self.measure(y, &[weight]);
+            loss += sample_loss;
+            accurate += sample_accurate;
+            total += weight;
+        }
+
+        (loss / total + self.penalty(), accurate / total)
+    }
+
+    /// Measures the weight penalty of all layers
+    fn penalty(&self) -> f32 {
+        match self.params.penalty {
+            Some(penalty) => self.layers
+                .iter()
+                .map(|layer| layer.penalty(penalty, self.params.penalize_biases))
+                .sum(),
+            None => 0.0
+        }
+    }
+
+    /// Scales sample `weight` by the class weight of label `y`
+    /// 
+    /// ## Note
+    /// The class of `y` is its `max_index`
+    fn sample_weight(&self, y: &Mat, weight: f32) -> f32 {
+        match &self.params.class_weights {
+            Some(class_weights) => weight * class_weights[y.max_index().0],
+            None => weight
+        }
+    }
+
+    /// Measures the `weights` scaled `(loss, accurate_predictions)` 
+    /// of the cached predictions against labels `y`
+    fn measure(&self, y: &Mat, weights: &[f32]) -> (f32, f32) {
+        let a_out = &self.acts[Rev(0)];
+        let loss_fn = self.params.loss_fn();
+        let mut loss = 0.0;
+        let mut accurate = 0.0;
+
+        // fused losses are measured from the logits
+        let measured = match loss_fn.is_fused() {
+            true => &self.layers[Rev(0)].sums,
+            false => a_out
+        };
+
+        for (j, weight) in weights.iter().enumerate() {
+            let (y, a) = (y.column(j), a_out.column(j));
+            loss += weight * loss_fn.loss(&y, &measured.column(j));
+
+            if a.max_index() == y.max_index() {
+                accurate += weight;
+            }
+        }
+
+        (loss, accurate)
+    }
+
     /// Measures `accuracte_predictions / samples`
     /// 
     /// ## TODO
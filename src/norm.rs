@@ -0,0 +1,286 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    clip::Clip,
+    matrix::{Mat, MatBase},
+    optimizer::{Optimizer, State}
+};
+
+/// Added to the variance before normalizing
+const EPSILON: f32 = 1e-5;
+/// Decay of the running batch statistics
+const MOMENTUM: f32 = 0.9;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum Norm {
+    /// Normalizes every node over the samples of a batch
+    Batch,
+    /// Normalizes every sample over the nodes of a layer
+    Layer
+}
+
+/// Normalizes the outputs `Z ₗ` of a layer
+///
+/// ## Note
+/// Propagated matrices hold one sample per column
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NormLayer {
+    norm:       Norm,
+    gamma:      Mat,
+    g_grad:     Mat,
+    g_grad_acc: Mat,
+    g_state:    State,
+    beta:       Mat,
+    b_grad:     Mat,
+    b_grad_acc: Mat,
+    b_state:    State,
+    /// Running batch mean
+    mean:       Mat,
+    /// Running batch variance
+    var:        Mat,
+    /// Cached normalized outputs `X̂`
+    normed:     Mat,
+    /// Cached inverse deviation of each normalized group
+    inv_std:    Vec<f32>,
+    /// Batches averaged into the running statistics while refreshing them
+    #[serde(default)]
+    refreshed:  Option<usize>
+}
+
+/// Scale, shift and running statistics of a `NormLayer`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NormParams {
+    gamma: Mat,
+    beta:  Mat,
+    mean:  Mat,
+    var:   Mat
+}
+
+impl NormParams {
+    /// Moves the parameters and running statistics
+    /// a fraction `t` of the way towards those of `layer`
+    pub fn blend(&mut self, layer: &NormLayer, t: f32) {
+        for (a, b) in [
+            (&mut self.gamma, &layer.gamma), 
+            (&mut self.beta, &layer.beta), 
+            (&mut self.mean, &layer.mean), 
+            (&mut self.var, &layer.var)
+        ] {
+            a.data_mut()
+                .iter_mut()
+                .zip(b.data())
+                .for_each(|(a, b)| *a += t * (b - *a));
+        }
+    }
+}
+
+impl NormLayer {
+    /// Creates a `NormLayer` over `n` nodes
+    pub fn new(norm: Norm, n: usize) -> Self {
+        Self {
+            norm,
+            gamma:      Mat::filled((n, 1), 1.0),
+            g_grad:     Mat::zeros((n, 1)),
+            g_grad_acc: Mat::zeros((n, 1)),
+            g_state:    State::new((n, 1)),
+            beta:       Mat::zeros((n, 1)),
+            b_grad:     Mat::zeros((n, 1)),
+            b_grad_acc: Mat::zeros((n, 1)),
+            b_state:    State::new((n, 1)),
+            mean:       Mat::zeros((n, 1)),
+            var:        Mat::filled((n, 1), 1.0),
+            normed:     Mat::zeros((n, 1)),
+            inv_std:    Vec::new(),
+            refreshed:  None
+        }
+    }
+
+    pub fn norm(&self) -> Norm {
+        self.norm
+    }
+
+    /// Returns `(groups, group_size)` of a `(row, col)` matrix
+    /// and a map from `(group, member)` to buffer index
+    fn layout(&self, (row, col): (usize, usize)) -> (usize, usize, impl Fn(usize, usize) -> usize) {
+        let norm = self.norm;
+        let index = move |g, k| match norm {
+            Norm::Batch => g * col + k,
+            Norm::Layer => k * col + g
+        };
+
+        match norm {
+            Norm::Batch => (row, col, index),
+            Norm::Layer => (col, row, index)
+        }
+    }
+
+    /// Normalizes `z` in place, using the running statistics
+    /// for batch normalization unless `training`
+    ///
+    /// ## Equations
+    /// - `X̂ = (Z - μ) / √(σ² + ε)`
+    /// - `Y = γ X̂ + β`
+    pub fn forward(&mut self, z: &mut Mat, training: bool) {
+        let (groups, size, index) = self.layout(z.shape());
+        let batch_stats = self.norm == Norm::Layer || training;
+
+        self.normed = Mat::zeros(z.shape());
+        self.inv_std = vec![0.0; groups];
+
+        for g in 0..groups {
+            let (mean, var) = if batch_stats {
+                let mean = (0..size).map(|k| z.data()[index(g, k)]).sum::<f32>() / size as f32;
+                let var = (0..size).map(|k| (z.data()[index(g, k)] - mean).powi(2)).sum::<f32>() / size as f32;
+                (mean, var)
+            } else {
+                (self.mean[(g, 0)], self.var[(g, 0)])
+            };
+
+            if self.norm == Norm::Batch && training {
+                // refreshed statistics are a plain average over batches
+                let momentum = match self.refreshed {
+                    Some(n) => n as f32 / (n + 1) as f32,
+                    None => MOMENTUM
+                };
+
+                self.mean[(g, 0)] = momentum * self.mean[(g, 0)] + (1.0 - momentum) * mean;
+                self.var[(g, 0)] = momentum * self.var[(g, 0)] + (1.0 - momentum) * var;
+            }
+
+            let inv_std = 1.0 / (var + EPSILON).sqrt();
+            self.inv_std[g] = inv_std;
+
+            for k in 0..size {
+                let i = index(g, k);
+                self.normed.data_mut()[i] = (z.data()[i] - mean) * inv_std;
+            }
+        }
+
+        if let Some(n) = &mut self.refreshed {
+            *n += 1;
+        }
+
+        let col = z.col();
+        for (i, n) in z.data_mut().iter_mut().enumerate() {
+            *n = self.gamma.data()[i / col] * self.normed.data()[i] + self.beta.data()[i / col];
+        }
+    }
+
+    /// Backpropagates error `grad` w.r.t. `Y` in place to `Z`,
+    /// setting the error of `γ` and `β`
+    ///
+    /// ## Equations
+    /// - `Δγ = Σ ϵ . X̂`, `Δβ = Σ ϵ`
+    /// - `ϵ' = γ ϵ`
+    /// - `ϵ_z = (N ϵ' - Σ ϵ' - X̂ Σ ϵ' . X̂) / (N √(σ² + ε))`
+    pub fn backward(&mut self, grad: &mut Mat) {
+        let (groups, size, index) = self.layout(grad.shape());
+        let col = grad.col();
+
+        self.g_grad.fill(0.0);
+        self.b_grad.fill(0.0);
+
+        let mut d_normed = Mat::zeros(grad.shape());
+
+        for (i, dy) in grad.data().iter().enumerate() {
+            let row = i / col;
+            self.g_grad.data_mut()[row] += dy * self.normed.data()[i];
+            self.b_grad.data_mut()[row] += dy;
+            d_normed.data_mut()[i] = dy * self.gamma.data()[row];
+        }
+
+        for g in 0..groups {
+            let mut sum = 0.0;
+            let mut sum_normed = 0.0;
+
+            for k in 0..size {
+                let i = index(g, k);
+                sum += d_normed.data()[i];
+                sum_normed += d_normed.data()[i] * self.normed.data()[i];
+            }
+
+            for k in 0..size {
+                let i = index(g, k);
+                let dx = size as f32 * d_normed.data()[i] - sum - self.normed.data()[i] * sum_normed;
+                grad.data_mut()[i] = self.inv_std[g] * dx / size as f32;
+            }
+        }
+    }
+
+    /// Copies the parameters and running statistics
+    pub fn params(&self) -> NormParams {
+        NormParams {
+            gamma: self.gamma.clone(),
+            beta:  self.beta.clone(),
+            mean:  self.mean.clone(),
+            var:   self.var.clone()
+        }
+    }
+
+    /// Exchanges the parameters and running statistics with `params`
+    pub fn swap_params(&mut self, params: &mut NormParams) {
+        std::mem::swap(&mut self.gamma, &mut params.gamma);
+        std::mem::swap(&mut self.beta, &mut params.beta);
+        std::mem::swap(&mut self.mean, &mut params.mean);
+        std::mem::swap(&mut self.var, &mut params.var);
+    }
+
+    /// Starts recomputing the running statistics from scratch,
+    /// averaging every following training batch equally
+    pub fn begin_refresh(&mut self) {
+        self.mean.fill(0.0);
+        self.var.fill(1.0);
+        self.refreshed = Some(0);
+    }
+
+    /// Stops recomputing the running statistics
+    pub fn end_refresh(&mut self) {
+        self.refreshed = None;
+    }
+
+    /// Accumulate error
+    pub fn accum_err(&mut self) {
+        self.g_grad_acc.add_assign(&self.g_grad);
+        self.b_grad_acc.add_assign(&self.b_grad);
+    }
+
+    /// Adds the error accumulated by `other`, clearing it
+    pub fn merge_err(&mut self, other: &mut NormLayer) {
+        self.g_grad_acc.add_assign(&other.g_grad_acc);
+        self.b_grad_acc.add_assign(&other.b_grad_acc);
+        other.clear_accum();
+    }
+
+    /// Average error accumulated over `samples`
+    pub fn average_err(&mut self, samples: usize) {
+        self.g_grad_acc.scale_assign(1.0 / samples as f32);
+        self.b_grad_acc.scale_assign(1.0 / samples as f32);
+    }
+
+    /// Squared L2 norm of accumulated error
+    pub fn err_norm_sq(&self) -> f32 {
+        self.g_grad_acc.data()
+            .iter()
+            .chain(self.b_grad_acc.data())
+            .map(|n| n * n)
+            .sum()
+    }
+
+    /// Clip accumulated error given the global error `norm`
+    pub fn clip_err(&mut self, clip: Clip, norm: f32) {
+        clip.apply(&mut self.g_grad_acc, norm);
+        clip.apply(&mut self.b_grad_acc, norm);
+    }
+
+    /// Apply accumulated error
+    pub fn apply_err(&mut self, optim: &dyn Optimizer, eta: f32) {
+        optim.step(&mut self.g_state, &mut self.gamma, &self.g_grad_acc, eta);
+        optim.step(&mut self.b_state, &mut self.beta, &self.b_grad_acc, eta);
+    }
+
+    /// Clears accumulation data
+    pub fn clear_accum(&mut self) {
+        self.g_grad_acc.fill(0.0);
+        self.b_grad_acc.fill(0.0);
+    }
+}
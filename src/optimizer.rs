@@ -0,0 +1,131 @@
+use serde::{Serialize, Deserialize};
+use crate::matrix::{Mat, MatBase};
+
+/// Added to the denominator of adaptive updates
+const EPSILON: f32 = 1e-8;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum Optim {
+    SGD,
+    Momentum(
+        f32 // momentum
+    ),
+    Nesterov(
+        f32 // momentum
+    ),
+    Adam(
+        f32, // beta1
+        f32 // beta2
+    ),
+    AdamW(
+        f32, // beta1
+        f32, // beta2
+        f32 // decay
+    ),
+    RMSProp(
+        f32 // rho
+    ),
+    Adagrad
+}
+
+/// Per-parameter optimizer state
+/// 
+/// ## Note
+/// The default empty state is sized on its first step
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct State {
+    /// Velocity or first moment estimate
+    pub m: Mat,
+    /// Second moment estimate
+    pub v: Mat,
+    /// Steps taken
+    pub t: i32
+}
+
+impl State {
+    /// Creates an empty state for a parameter of `shape`
+    pub fn new(shape: (usize, usize)) -> Self {
+        Self {
+            m: Mat::zeros(shape),
+            v: Mat::zeros(shape),
+            t: 0
+        }
+    }
+}
+
+/// Updates parameters from their gradients
+///
+/// ## Note
+/// `Optim` provides the built-in implementations
+pub trait Optimizer: Send + Sync {
+    /// Updates `param` along descent direction `grad` with learn rate `eta`,
+    /// keeping its history in `state`
+    fn step(&self, state: &mut State, param: &mut Mat, grad: &Mat, eta: f32);
+}
+
+impl Optimizer for Optim {
+    /// ## Equations
+    /// - SGD: `W += η ΔW`
+    /// - Momentum: `V = μ V + η ΔW`, `W += V`
+    /// - Nesterov: `V = μ V + η ΔW`, `W += μ V + η ΔW`
+    /// - Adam: `W += η m̂ / (√v̂ + ε)`
+    /// - AdamW: Adam, with `decay` applied to the weights as the model's `weight_decay`
+    /// - RMSProp: `v = ρ v + (1 - ρ) ΔW²`, `W += η ΔW / (√v + ε)`
+    /// - Adagrad: `v += ΔW²`, `W += η ΔW / (√v + ε)`
+    fn step(&self, state: &mut State, param: &mut Mat, grad: &Mat, eta: f32) {
+        assert_eq!(param.shape(), grad.shape());
+
+        if state.m.shape() != param.shape() {
+            *state = State::new(param.shape());
+        }
+
+        state.t += 1;
+
+        let t = state.t;
+        let params = param.data_mut().iter_mut();
+        let moments = state.m.data_mut().iter_mut().zip(state.v.data_mut().iter_mut());
+        let updates = params.zip(grad.data()).zip(moments);
+
+        match *self {
+            Optim::SGD => {
+                for ((w, g), _) in updates {
+                    *w += eta * g;
+                }
+            }
+            Optim::Momentum(mu) => {
+                for ((w, g), (m, _)) in updates {
+                    *m = mu * *m + eta * g;
+                    *w += *m;
+                }
+            }
+            Optim::Nesterov(mu) => {
+                for ((w, g), (m, _)) in updates {
+                    *m = mu * *m + eta * g;
+                    *w += mu * *m + eta * g;
+                }
+            }
+            Optim::Adam(beta1, beta2) | Optim::AdamW(beta1, beta2, _) => {
+                let correct1 = 1.0 - beta1.powi(t);
+                let correct2 = 1.0 - beta2.powi(t);
+
+                for ((w, g), (m, v)) in updates {
+                    *m = beta1 * *m + (1.0 - beta1) * g;
+                    *v = beta2 * *v + (1.0 - beta2) * g * g;
+                    *w += eta * (*m / correct1) / ((*v / correct2).sqrt() + EPSILON);
+                }
+            }
+            Optim::RMSProp(rho) => {
+                for ((w, g), (_, v)) in updates {
+                    *v = rho * *v + (1.0 - rho) * g * g;
+                    *w += eta * g / (v.sqrt() + EPSILON);
+                }
+            }
+            Optim::Adagrad => {
+                for ((w, g), (_, v)) in updates {
+                    *v += g * g;
+                    *w += eta * g / (v.sqrt() + EPSILON);
+                }
+            }
+        }
+    }
+}
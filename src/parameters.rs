@@ -1,36 +1,162 @@
+use std::sync::Arc;
 use serde::{Deserialize, Serialize};
-use crate::{activation::Act, cost::Cost, network::FeedForward, weight_init::Weight};
+use crate::{
+    activation::Act, 
+    bias_init::Bias, 
+    clip::Clip,
+    dropout::Dropout,
+    cost::Cost, 
+    loss::Loss, 
+    network::FeedForward, 
+    norm::Norm,
+    optimizer::{Optim, Optimizer},
+    penalty::Penalty,
+    schedule::Schedule,
+    stopping::{EarlyStop, Metric},
+    weight_init::Weight
+};
 
 /// Default learn rate
 const LEARN_RATE: f32 = 0.01;
 /// Default momentum rate
 const MOMENTUM: f32 = 0.80;
+/// Default optimizer
+const OPTIM: Optim = Optim::Momentum(MOMENTUM);
+/// Default learn rate schedule
+const SCHEDULE: Schedule = Schedule::Constant;
+/// Default gradient clipping
+const CLIP: Option<Clip> = None;
+/// Default weight penalty
+const PENALTY: Option<Penalty> = None;
+/// Default decoupled weight decay rate
+const WEIGHT_DECAY: f32 = 0.0;
 /// Default batch size
 const BATCH_SIZE: usize = 32;
+/// Default training thread count
+const THREADS: usize = 1;
 /// Default train epochs count
 const EPOCHS: usize = 5;
+/// Default fraction of training data held out for validation
+const VALID_SPLIT: f32 = 0.0;
 /// Default activation function
 const ACTIVATION: Act = Act::Tanh;
 /// Default cost function
 const COST: Cost = Cost::MSE;
 /// Default weight initialization method
 const WEIGHT: Weight = Weight::Range(-0.2, 0.2);
+/// Default bias initialization method
+const BIAS: Bias = Bias::Value(0.0);
 /// Default model save path
 const SAVE_PATH: &str = "src/models/model";
+/// Default training checkpoint path
+const CHECKPOINT_PATH: &str = "src/models/checkpoint";
+
+// Defaults of the fields missing from models saved before they were added
+
+fn default_optim() -> Optim {
+    OPTIM
+}
+
+fn default_schedule() -> Schedule {
+    SCHEDULE
+}
+
+fn default_threads() -> usize {
+    THREADS
+}
+
+fn default_bias() -> Bias {
+    BIAS
+}
+
+fn default_checkpoint_path() -> String {
+    CHECKPOINT_PATH.to_string()
+}
+
+fn per_layer<const L: usize, T: Clone>() -> Vec<Option<T>> {
+    vec![None; L-1]
+}
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Params<const L: usize> {
     pub form: Vec<usize>,
     pub learn_rate: f32,
-    pub momentum: f32,
+    #[serde(default = "default_optim")]
+    pub optim: Optim,
+    #[serde(default = "default_schedule")]
+    pub schedule: Schedule,
+    #[serde(default)]
+    pub clip: Option<Clip>,
+    #[serde(default)]
+    pub penalty: Option<Penalty>,
+    #[serde(default)]
+    pub penalize_biases: bool,
+    #[serde(default)]
+    pub weight_decay: f32,
+    #[serde(default)]
+    pub max_norm: Option<f32>,
+    /// Decay of the exponential moving average of the parameters
+    #[serde(default)]
+    pub ema: Option<f32>,
+    /// Epoch from which the parameters are stochastically weight averaged
+    #[serde(default)]
+    pub swa: Option<usize>,
     pub batch_size: usize,
+    /// Worker threads splitting each batch
+    /// 
+    /// ## Note
+    /// Batch normalized models train on a single thread
+    #[serde(default = "default_threads")]
+    pub threads: usize,
     pub epochs:  usize,
+    /// Fraction of training data held out for validation
+    /// when `train` isn't given a validation set
+    #[serde(default)]
+    pub valid_split: f32,
+    #[serde(default)]
+    pub early_stop: Option<EarlyStop>,
     pub weight: Weight,
+    /// Per-layer overrides of `weight`
+    #[serde(default = "per_layer::<L, _>")]
+    pub layer_weights: Vec<Option<Weight>>,
+    #[serde(default = "default_bias")]
+    pub bias: Bias,
+    /// Per-layer overrides of `bias`
+    #[serde(default = "per_layer::<L, _>")]
+    pub layer_biases: Vec<Option<Bias>>,
+    /// Dropout applied to the output of each layer
+    #[serde(default = "per_layer::<L, _>")]
+    pub dropout: Vec<Option<Dropout>>,
+    /// Normalization of the outputs of each layer
+    #[serde(default = "per_layer::<L, _>")]
+    pub norm: Vec<Option<Norm>>,
     pub act:       Act,
     pub cost:      Cost,
     pub shuffle:   bool,
     pub verbose:   bool,
-    pub save_path: String
+    pub save_path: String,
+    /// Epochs between training checkpoints
+    #[serde(default)]
+    pub checkpoint_every: Option<usize>,
+    #[serde(default = "default_checkpoint_path")]
+    pub checkpoint_path: String,
+    /// Seed of the random stream of shuffling and dropout
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub class_weights: Option<Vec<f32>>,
+    /// Custom loss overriding `cost`
+    /// 
+    /// ## Note
+    /// Custom losses aren't saved with the model
+    #[serde(skip)]
+    pub loss: Option<Arc<dyn Loss>>,
+    /// Custom optimizer overriding `optim`
+    /// 
+    /// ## Note
+    /// Custom optimizers aren't saved with the model
+    #[serde(skip)]
+    pub custom_optim: Option<Arc<dyn Optimizer>>
 }
 
 impl<const L: usize> From<[usize; L]> for Params<L> {
@@ -38,15 +164,37 @@ impl<const L: usize> From<[usize; L]> for Params<L> {
         Self {
             form: form.to_vec(),
             learn_rate: LEARN_RATE,
-            momentum: MOMENTUM,
+            optim: OPTIM,
+            schedule: SCHEDULE,
+            clip: CLIP,
+            penalty: PENALTY,
+            penalize_biases: false,
+            weight_decay: WEIGHT_DECAY,
+            max_norm: None,
+            ema: None,
+            swa: None,
             batch_size: BATCH_SIZE,
+            threads: THREADS,
             epochs: EPOCHS,
+            valid_split: VALID_SPLIT,
+            early_stop: None,
             weight: WEIGHT,
+            layer_weights: vec![None; L-1],
+            bias: BIAS,
+            layer_biases: vec![None; L-1],
+            dropout: vec![None; L-1],
+            norm: vec![None; L-1],
             act: ACTIVATION,
             cost: COST,
             shuffle: true,
             verbose: true,
-            save_path: SAVE_PATH.to_string()
+            save_path: SAVE_PATH.to_string(),
+            checkpoint_every: None,
+            checkpoint_path: CHECKPOINT_PATH.to_string(),
+            seed: None,
+            class_weights: None,
+            loss: None,
+            custom_optim: None
         }    
     }
 }
@@ -56,6 +204,40 @@ impl<const L: usize> Params<L> {
         Self::from(form)
     }
 
+    /// Returns the custom `loss`, falling back to `cost`
+    pub fn loss_fn(&self) -> &dyn Loss {
+        match &self.loss {
+            Some(loss) => loss.as_ref(),
+            None => &self.cost
+        }
+    }
+
+    /// Returns the custom optimizer, falling back to `optim`
+    pub fn optim_fn(&self) -> &dyn Optimizer {
+        match &self.custom_optim {
+            Some(optim) => optim.as_ref(),
+            None => &self.optim
+        }
+    }
+
+    /// Returns the `weight_init` method of the layer going `layer → layer+1`
+    pub fn weight_for(&self, layer: usize) -> Weight {
+        self.layer_weights[layer].unwrap_or(self.weight)
+    }
+
+    /// Returns the decoupled weight decay rate, set by `AdamW` or `weight_decay`
+    pub fn decay(&self) -> f32 {
+        match self.optim {
+            Optim::AdamW(_, _, decay) => decay,
+            _ => self.weight_decay
+        }
+    }
+
+    /// Returns the `bias_init` method of the layer going `layer → layer+1`
+    pub fn bias_for(&self, layer: usize) -> &Bias {
+        self.layer_biases[layer].as_ref().unwrap_or(&self.bias)
+    }
+
     /// Build `Net` 
     pub fn build(&self) -> FeedForward<L> {
         FeedForward::from(self.clone())
@@ -68,29 +250,159 @@ impl<const L: usize> Params<L> {
     } 
 
     /// Set model `momentum` rate
+    /// 
+    /// ## Note
+    /// - Only adjusts the `Momentum` and `Nesterov` optimizers
+    /// - Panics on any other optimizer, including the `SGD` selected
+    ///   with `optimizer`, so set a momentum based one first
     pub fn momentum(&mut self, momentum: f32) -> &mut Self {
-        self.momentum = momentum;
+        self.optim = match self.optim {
+            Optim::Momentum(_) => Optim::Momentum(momentum),
+            Optim::Nesterov(_) => Optim::Nesterov(momentum),
+            _ => panic!("momentum requires a momentum based optimizer")
+        };
         self
     } 
 
+    /// Set model `optimizer`
+    pub fn optimizer(&mut self, optim: Optim) -> &mut Self {
+        self.optim = optim;
+        self
+    }
+
+    /// Set model custom `optimizer`, overriding `optim`
+    pub fn custom_optimizer<T: Optimizer + 'static>(&mut self, optimizer: T) -> &mut Self {
+        self.custom_optim = Some(Arc::new(optimizer));
+        self
+    }
+
+    /// Set model `learn_rate` schedule
+    pub fn schedule(&mut self, schedule: Schedule) -> &mut Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Set model gradient `clip` method
+    pub fn clip(&mut self, clip: Clip) -> &mut Self {
+        self.clip = Some(clip);
+        self
+    }
+
+    /// Set model weight `penalty`
+    pub fn penalty(&mut self, penalty: Penalty) -> &mut Self {
+        self.penalty = Some(penalty);
+        self
+    }
+
+    /// Set whether model `penalty` applies to biases
+    pub fn penalize_biases(&mut self, penalize_biases: bool) -> &mut Self {
+        self.penalize_biases = penalize_biases;
+        self
+    }
+
+    /// Set model decoupled `weight_decay` rate
+    /// 
+    /// ## Note
+    /// Decays weights only, and can't be combined with `AdamW`'s own decay
+    pub fn weight_decay(&mut self, weight_decay: f32) -> &mut Self {
+        self.weight_decay = weight_decay;
+        self
+    }
+
+    /// Set model `max_norm` of each node's incoming weights
+    pub fn max_norm(&mut self, max_norm: f32) -> &mut Self {
+        self.max_norm = Some(max_norm);
+        self
+    }
+
     /// Set model `batch_size`
     pub fn batch_size(&mut self, batch_size: usize) -> &mut Self {
         self.batch_size = batch_size;
         self
     }
 
+    /// Set the worker `threads` splitting each batch
+    pub fn threads(&mut self, threads: usize) -> &mut Self {
+        assert!(threads > 0);
+        self.threads = threads;
+        self
+    }
+
+    /// Set an exponential moving average of the parameters with `decay`
+    pub fn ema(&mut self, decay: f32) -> &mut Self {
+        assert!((0.0..1.0).contains(&decay));
+        self.ema = Some(decay);
+        self
+    }
+
+    /// Set a stochastic weight average of the parameters
+    /// over every epoch from `start`
+    pub fn swa(&mut self, start: usize) -> &mut Self {
+        self.swa = Some(start);
+        self
+    }
+
     /// Set model train `epochs`
     pub fn epochs(&mut self, epochs: usize) -> &mut Self {
         self.epochs = epochs;
         self
     }
 
+    /// Set the fraction of training data held out for validation
+    pub fn valid_split(&mut self, valid_split: f32) -> &mut Self {
+        assert!((0.0..1.0).contains(&valid_split));
+        self.valid_split = valid_split;
+        self
+    }
+
+    /// Set early stopping once `metric` hasn't improved 
+    /// by more than `min_delta` for `patience` epochs
+    pub fn early_stop(&mut self, metric: Metric, patience: usize, min_delta: f32) -> &mut Self {
+        self.early_stop = Some(EarlyStop { metric, patience, min_delta });
+        self
+    }
+
     /// Set model `weight_init` method
     pub fn weight(&mut self, weight_init: Weight) -> &mut Self {
         self.weight = weight_init;
         self
     }
 
+    /// Set `weight_init` method of the layer going `layer → layer+1`
+    pub fn layer_weight(&mut self, layer: usize, weight_init: Weight) -> &mut Self {
+        self.layer_weights[layer] = Some(weight_init);
+        self
+    }
+
+    /// Set model `bias_init` method
+    pub fn bias(&mut self, bias_init: Bias) -> &mut Self {
+        self.bias = bias_init;
+        self
+    }
+
+    /// Set `bias_init` method of the layer going `layer → layer+1`
+    pub fn layer_bias(&mut self, layer: usize, bias_init: Bias) -> &mut Self {
+        self.layer_biases[layer] = Some(bias_init);
+        self
+    }
+
+    /// Set `dropout` on the output of the hidden layer going `layer → layer+1`
+    pub fn dropout(&mut self, layer: usize, dropout: Dropout) -> &mut Self {
+        assert!(layer + 2 < L, "dropout can't apply to the output layer");
+
+        let (Dropout::Standard(rate) | Dropout::Alpha(rate)) = dropout;
+        assert!((0.0..1.0).contains(&rate), "dropout rate must be in [0, 1)");
+
+        self.dropout[layer] = Some(dropout);
+        self
+    }
+
+    /// Set `norm` on the outputs of the layer going `layer → layer+1`
+    pub fn norm(&mut self, layer: usize, norm: Norm) -> &mut Self {
+        self.norm[layer] = Some(norm);
+        self
+    }
+
     /// Set model `activation`
     pub fn activation(&mut self, act: Act) -> &mut Self {
         self.act = act;
@@ -103,6 +415,12 @@ impl<const L: usize> Params<L> {
         self
     }
 
+    /// Set model custom `loss`, overriding `cost`
+    pub fn loss<T: Loss + 'static>(&mut self, loss: T) -> &mut Self {
+        self.loss = Some(Arc::new(loss));
+        self
+    }
+
     /// Set whether model shuffles its data per epoch
     pub fn shuffle(&mut self, shuffle: bool) -> &mut Self {
         self.shuffle = shuffle;
@@ -120,4 +438,24 @@ impl<const L: usize> Params<L> {
         self.save_path = save_path.to_string();
         self
     }
+
+    /// Set training checkpoints saved to `path` `every` epochs
+    pub fn checkpoint(&mut self, every: usize, path: &str) -> &mut Self {
+        assert!(every > 0);
+        self.checkpoint_every = Some(every);
+        self.checkpoint_path = path.to_string();
+        self
+    }
+
+    /// Set the `seed` of the random stream of shuffling and dropout
+    pub fn seed(&mut self, seed: u64) -> &mut Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Set model per-class loss `class_weights`
+    pub fn class_weights(&mut self, class_weights: &[f32]) -> &mut Self {
+        self.class_weights = Some(class_weights.to_vec());
+        self
+    }
 }
\ No newline at end of file
@@ -0,0 +1,38 @@
+use serde::{Serialize, Deserialize};
+
+use crate::matrix::{Mat, MatBase};
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum Penalty {
+    L1(f32),
+    L2(f32),
+    ElasticNet(
+        f32, // l1
+        f32 // l2
+    )
+}
+
+impl Penalty {
+    /// Applies penalty function to parameters `w`
+    pub fn value(&self, w: &Mat) -> f32 {
+        let l1: f32 = w.data().iter().map(|n| n.abs()).sum();
+        let l2: f32 = w.data().iter().map(|n| n * n).sum();
+
+        match self {
+            Penalty::L1(l) => l * l1,
+            Penalty::L2(l) => l * l2,
+            Penalty::ElasticNet(a, b) => a * l1 + b * l2
+        }
+    }
+
+    /// Applies penalty derivative to parameter `w`
+    pub fn deriv(&self, w: f32) -> f32 {
+        let sign = if w == 0.0 { 0.0 } else { w.signum() };
+
+        match self {
+            Penalty::L1(l) => l * sign,
+            Penalty::L2(l) => 2.0 * l * w,
+            Penalty::ElasticNet(a, b) => a * sign + 2.0 * b * w
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use serde::{Serialize, Deserialize};
+
+use crate::matrix::{Mat, MatBase};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Scope {
+    /// Prunes the same fraction of every layer
+    Layer,
+    /// Prunes the smallest weights across all layers
+    Global
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum Prune {
+    /// Prunes to the target sparsity at once
+    OneShot(Scope),
+    /// Ramps the sparsity up cubically over `steps` rounds,
+    /// retraining for `epochs` after every round
+    Gradual(
+        Scope,
+        usize, // steps
+        usize // epochs
+    )
+}
+
+impl Prune {
+    pub fn scope(&self) -> Scope {
+        match self {
+            Prune::OneShot(scope) | Prune::Gradual(scope, ..) => *scope
+        }
+    }
+
+    /// Returns the sparsity of every round given the final `sparsity`
+    ///
+    /// ## Equations
+    /// - `sₖ = s (1 - (1 - k / steps)³)`
+    pub fn rounds(&self, sparsity: f32) -> Vec<f32> {
+        match self {
+            Prune::OneShot(_) => vec![sparsity],
+            Prune::Gradual(_, steps, _) => (1..=*steps)
+                .map(|k| sparsity * (1.0 - (1.0 - k as f32 / *steps as f32).powi(3)))
+                .collect()
+        }
+    }
+}
+
+/// Accuracy and sparsity after a pruning round
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct PruneStep {
+    /// Fraction of zeroed weights
+    pub sparsity: f32,
+    pub loss: f32,
+    pub accuracy: f32
+}
+
+/// Computes masks keeping all but the `sparsity` fraction
+/// of smallest-magnitude `weights` within `scope`
+pub fn magnitude_masks(weights: &[&Mat], sparsity: f32, scope: Scope) -> Vec<Mat> {
+    let mut masks: Vec<_> = weights
+        .iter()
+        .map(|w| Mat::filled(w.shape(), 1.0))
+        .collect();
+
+    // pools of (layer, index) ranked by magnitude
+    let pools: Vec<Vec<(usize, usize)>> = match scope {
+        Scope::Layer => weights
+            .iter()
+            .enumerate()
+            .map(|(l, w)| (0..w.data().len()).map(|i| (l, i)).collect())
+            .collect(),
+        Scope::Global => vec![weights
+            .iter()
+            .enumerate()
+            .flat_map(|(l, w)| (0..w.data().len()).map(move |i| (l, i)))
+            .collect()]
+    };
+
+    for mut pool in pools {
+        pool.sort_by(|&(la, a), &(lb, b)| {
+            weights[la].data()[a].abs().total_cmp(&weights[lb].data()[b].abs())
+        });
+
+        let pruned = (pool.len() as f32 * sparsity).round() as usize;
+        for &(l, i) in pool.iter().take(pruned) {
+            masks[l].data_mut()[i] = 0.0;
+        }
+    }
+
+    masks
+}
+
+/// Compressed sparse row matrix
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Csr {
+    pub shape: (usize, usize),
+    /// Offsets of each row into `cols` and `values`
+    pub row_ptr: Vec<usize>,
+    pub cols: Vec<usize>,
+    pub values: Vec<f32>
+}
+
+impl Csr {
+    /// Compresses the nonzero elements of `mat`
+    pub fn from_dense(mat: &Mat) -> Self {
+        let (row, col) = mat.shape();
+        let mut csr = Self {
+            shape: (row, col),
+            row_ptr: vec![0],
+            cols: Vec::new(),
+            values: Vec::new()
+        };
+
+        for r in mat.data().chunks(col) {
+            for (c, &n) in r.iter().enumerate() {
+                if n != 0.0 {
+                    csr.cols.push(c);
+                    csr.values.push(n);
+                }
+            }
+            csr.row_ptr.push(csr.values.len());
+        }
+
+        csr
+    }
+
+    /// Expands into a dense matrix
+    pub fn to_dense(&self) -> Mat {
+        let (row, col) = self.shape;
+        let mut buf = vec![0.0; row * col];
+
+        for r in 0..row {
+            for i in self.row_ptr[r]..self.row_ptr[r+1] {
+                buf[r * col + self.cols[i]] = self.values[i];
+            }
+        }
+
+        Mat::from_vec(self.shape, buf)
+    }
+}
@@ -0,0 +1,69 @@
+use rand::{Rng, RngCore};
+use serde::{Serialize, Deserialize};
+
+/// PCG multiplier
+const MULTIPLIER: u64 = 6364136223846793005;
+
+/// Permuted congruential generator (PCG-XSH-RR)
+///
+/// ## Note
+/// Unlike `rand::thread_rng`, its state can be saved
+/// so a checkpointed run replays the same random stream
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Pcg {
+    state: u64,
+    inc: u64
+}
+
+impl Pcg {
+    /// Creates a generator from `seed`
+    pub fn new(seed: u64) -> Self {
+        let mut pcg = Self {
+            state: 0,
+            inc: (seed << 1) | 1
+        };
+
+        pcg.next_u32();
+        pcg.state = pcg.state.wrapping_add(seed);
+        pcg.next_u32();
+        pcg
+    }
+
+    /// Creates a generator from an entropy seeded seed
+    pub fn from_entropy() -> Self {
+        Self::new(rand::thread_rng().gen())
+    }
+}
+
+impl Default for Pcg {
+    fn default() -> Self {
+        Self::from_entropy()
+    }
+}
+
+impl RngCore for Pcg {
+    fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+        self.state = old.wrapping_mul(MULTIPLIER).wrapping_add(self.inc);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        (self.next_u32() as u64) << 32 | self.next_u32() as u64
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
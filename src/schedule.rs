@@ -0,0 +1,135 @@
+use std::f32::consts::PI;
+use serde::{Serialize, Deserialize};
+
+/// Fraction of a one-cycle run spent increasing the learn rate
+const ONE_CYCLE_WARMUP: f32 = 0.3;
+/// Ratio of the final to the initial learn rate of a one-cycle run
+const ONE_CYCLE_FINAL: f32 = 1e-3;
+/// Minimum improvement of the watched loss to reset plateau patience
+const PLATEAU_MIN_DELTA: f32 = 1e-4;
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub enum Schedule {
+    Constant,
+    /// Decays the rate by `gamma` every `step` epochs
+    Step(
+        usize, // step
+        f32 // gamma
+    ),
+    /// Decays the rate by `gamma` every epoch
+    Exponential(
+        f32 // gamma
+    ),
+    /// Anneals the rate to `min` over `period` epochs,
+    /// restarting with a period grown by `mult`
+    CosineRestarts(
+        usize, // period
+        f32, // mult
+        f32 // min
+    ),
+    /// Ramps the rate up linearly over `steps` batches
+    Warmup(
+        usize // steps
+    ),
+    /// Ramps the rate up to `max` then anneals it over the whole run
+    OneCycle(
+        f32 // max
+    ),
+    /// Decays the rate by `factor` whenever the validation loss
+    /// hasn't improved for `patience` epochs
+    Plateau(
+        f32, // factor
+        usize // patience
+    )
+}
+
+/// Tracks the state of a learn rate `Schedule`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Scheduler {
+    schedule: Schedule,
+    /// Accumulated plateau decay
+    scale: f32,
+    /// Best observed loss
+    best: Option<f32>,
+    /// Epochs without improvement
+    wait: usize
+}
+
+impl Scheduler {
+    pub fn new(schedule: Schedule) -> Self {
+        match schedule {
+            Schedule::Step(every, _) => assert!(every > 0, "step must be positive"),
+            Schedule::CosineRestarts(period, mult, _) => {
+                assert!(period > 0, "period must be positive");
+                assert!(mult >= 1.0, "mult must be at least 1");
+            }
+            _ => ()
+        }
+
+        Self {
+            schedule,
+            scale: 1.0,
+            best: None,
+            wait: 0
+        }
+    }
+
+    /// Returns the learn rate at batch `step` of a run lasting `epochs`
+    /// of `steps_per_epoch` batches each, given initial rate `base`
+    pub fn rate(&self, base: f32, step: usize, steps_per_epoch: usize, epochs: usize) -> f32 {
+        let epoch = step / steps_per_epoch;
+
+        match self.schedule {
+            Schedule::Constant => base,
+            Schedule::Step(every, gamma) => base * gamma.powi((epoch / every) as i32),
+            Schedule::Exponential(gamma) => base * gamma.powi(epoch as i32),
+            Schedule::CosineRestarts(period, mult, min) => {
+                // locate the current cycle in fractional epochs
+                let mut t = step as f32 / steps_per_epoch as f32;
+                let mut period = period as f32;
+
+                while t >= period {
+                    t -= period;
+                    period *= mult;
+                }
+
+                min + 0.5 * (base - min) * (1.0 + (PI * t / period).cos())
+            }
+            Schedule::Warmup(steps) => base * ((step + 1) as f32 / steps as f32).min(1.0),
+            Schedule::OneCycle(max) => {
+                let progress = step as f32 / (steps_per_epoch * epochs) as f32;
+
+                if progress < ONE_CYCLE_WARMUP {
+                    base + (max - base) * progress / ONE_CYCLE_WARMUP
+                } else {
+                    let t = (progress - ONE_CYCLE_WARMUP) / (1.0 - ONE_CYCLE_WARMUP);
+                    let min = base * ONE_CYCLE_FINAL;
+                    min + 0.5 * (max - min) * (1.0 + (PI * t).cos())
+                }
+            }
+            Schedule::Plateau(..) => base * self.scale
+        }
+    }
+
+    /// Observes the watched `loss` at the end of an epoch
+    pub fn observe(&mut self, loss: f32) {
+        if let Schedule::Plateau(factor, patience) = self.schedule {
+            let improved = match self.best {
+                Some(best) => loss < best - PLATEAU_MIN_DELTA,
+                None => true
+            };
+
+            if improved {
+                self.best = Some(loss);
+                self.wait = 0;
+            } else {
+                self.wait += 1;
+            }
+
+            if self.wait > patience {
+                self.scale *= factor;
+                self.wait = 0;
+            }
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use serde::{Serialize, Deserialize};
+
+use crate::history::Epoch;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Metric {
+    /// Minimizes the loss
+    Loss,
+    /// Maximizes the accuracy
+    Accuracy
+}
+
+impl Metric {
+    /// Returns the metric of `epoch`, preferring its validation stats
+    pub fn value(&self, epoch: &Epoch) -> f32 {
+        match self {
+            Metric::Loss => epoch.valid_loss.unwrap_or(epoch.loss),
+            Metric::Accuracy => epoch.valid_accuracy.unwrap_or(epoch.accuracy)
+        }
+    }
+
+    /// Returns whether `value` improves on `best` by more than `min_delta`
+    pub fn improves(&self, value: f32, best: f32, min_delta: f32) -> bool {
+        match self {
+            Metric::Loss => value < best - min_delta,
+            Metric::Accuracy => value > best + min_delta
+        }
+    }
+}
+
+/// Stops training once `metric` hasn't improved
+/// by more than `min_delta` for `patience` epochs
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct EarlyStop {
+    pub metric: Metric,
+    pub patience: usize,
+    pub min_delta: f32
+}
+
+/// Tracks the state of an `EarlyStop` rule
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Stopper {
+    rule: EarlyStop,
+    /// Best observed metric
+    best: Option<f32>,
+    /// Epoch of the best observed metric
+    best_epoch: usize,
+    /// Epochs without improvement
+    wait: usize
+}
+
+impl Stopper {
+    pub fn new(rule: EarlyStop) -> Self {
+        Self {
+            rule,
+            best: None,
+            best_epoch: 0,
+            wait: 0
+        }
+    }
+
+    /// Returns the epoch of the best observed metric
+    pub fn best_epoch(&self) -> usize {
+        self.best_epoch
+    }
+
+    /// Observes the stats of `epoch`, returning whether they improved
+    pub fn observe(&mut self, epoch: usize, stats: &Epoch) -> bool {
+        let value = self.rule.metric.value(stats);
+
+        let improved = match self.best {
+            Some(best) => self.rule.metric.improves(value, best, self.rule.min_delta),
+            None => true
+        };
+
+        if improved {
+            self.best = Some(value);
+            self.best_epoch = epoch;
+            self.wait = 0;
+            true
+        } else {
+            self.wait += 1;
+            false
+        }
+    }
+
+    /// Returns whether training should stop
+    pub fn should_stop(&self) -> bool {
+        self.wait >= self.rule.patience
+    }
+}
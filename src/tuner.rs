@@ -0,0 +1,383 @@
+use std::io::Error;
+use rand::{seq::SliceRandom, Rng};
+use serde::{Serialize, Deserialize};
+
+use crate::{
+    activation::Act,
+    matrix::Mat,
+    optimizer::Optim,
+    parameters::Params,
+    rng::Pcg,
+    weight_init::Weight
+};
+
+/// Default fraction of training data held out to score candidates
+const VALID_SPLIT: f32 = 0.2;
+/// Default leaderboard save path
+const SAVE_PATH: &str = "src/models/leaderboard.json";
+
+/// Candidate values of searched `Params` fields
+///
+/// ## Note
+/// Fields left empty keep the value of the base `Params`
+#[derive(Clone, Default)]
+pub struct Space {
+    pub learn_rate: Vec<f32>,
+    pub momentum: Vec<f32>,
+    pub batch_size: Vec<usize>,
+    /// Hidden layer widths of `form`
+    pub widths: Vec<Vec<usize>>,
+    pub act: Vec<Act>,
+    pub weight: Vec<Weight>
+}
+
+impl Space {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set candidate `learn_rate` values
+    pub fn learn_rate(&mut self, values: &[f32]) -> &mut Self {
+        self.learn_rate = values.to_vec();
+        self
+    }
+
+    /// Set candidate `momentum` values
+    pub fn momentum(&mut self, values: &[f32]) -> &mut Self {
+        self.momentum = values.to_vec();
+        self
+    }
+
+    /// Set candidate `batch_size` values
+    pub fn batch_size(&mut self, values: &[usize]) -> &mut Self {
+        self.batch_size = values.to_vec();
+        self
+    }
+
+    /// Set candidate hidden layer `widths`
+    pub fn widths(&mut self, values: &[&[usize]]) -> &mut Self {
+        self.widths = values.iter().map(|w| w.to_vec()).collect();
+        self
+    }
+
+    /// Set candidate `activation` functions
+    pub fn activation(&mut self, values: &[Act]) -> &mut Self {
+        self.act = values.to_vec();
+        self
+    }
+
+    /// Set candidate `weight_init` methods
+    pub fn weight(&mut self, values: &[Weight]) -> &mut Self {
+        self.weight = values.to_vec();
+        self
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Search {
+    /// Tries every combination of the space
+    Grid,
+    /// Tries `trials` random combinations of the space
+    Random(
+        usize // trials
+    ),
+    /// Trains `candidates` random combinations on a growing epoch budget,
+    /// keeping the best `1 / factor` after every round
+    Halving(
+        usize, // candidates
+        usize // factor
+    )
+}
+
+/// A single combination of searched `Params` fields
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Config {
+    pub learn_rate: f32,
+    /// Momentum of momentum based optimizers, `None` for others
+    pub momentum: Option<f32>,
+    pub batch_size: usize,
+    pub widths: Vec<usize>,
+    pub act: Act,
+    pub weight: Weight
+}
+
+impl Config {
+    /// Applies the configuration to `params`
+    /// 
+    /// ## Note
+    /// `momentum` only adjusts a `Momentum` or `Nesterov` optimizer
+    pub fn apply<const L: usize>(&self, params: &mut Params<L>) {
+        params.learn_rate = self.learn_rate;
+        params.batch_size = self.batch_size;
+        params.form[1..L-1].copy_from_slice(&self.widths);
+        params.act = self.act;
+        params.weight = self.weight;
+
+        if let (Some(momentum), Optim::Momentum(_) | Optim::Nesterov(_)) = (self.momentum, params.optim) {
+            params.momentum(momentum);
+        }
+    }
+}
+
+/// A scored `Config`
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Trial {
+    pub config: Config,
+    /// Trained epochs
+    pub epochs: usize,
+    /// Best validation loss
+    pub score: f32,
+    /// Validation accuracy of the final epoch
+    pub accuracy: f32
+}
+
+/// Trials ranked by descending epoch budget, then ascending score
+/// 
+/// ## Note
+/// Scores of different budgets aren't comparable, so candidates
+/// eliminated early by successive halving rank below the survivors
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Leaderboard {
+    pub trials: Vec<Trial>
+}
+
+impl Leaderboard {
+    /// Returns the best scoring trial
+    pub fn best(&self) -> Option<&Trial> {
+        self.trials.first()
+    }
+
+    /// Saves the leaderboard as JSON to `path`, creating its directory
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        let json = serde_json::to_string_pretty(self)?;
+        let path = std::env::current_dir()?.join(path);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, json)
+    }
+
+    fn rank(&mut self) {
+        self.trials.sort_by(|a, b| b.epochs.cmp(&a.epochs).then(a.score.total_cmp(&b.score)));
+    }
+}
+
+/// Searches a `Space` of `Params` fields, scoring each
+/// candidate by its loss on a held out validation split
+pub struct Tuner<const L: usize> {
+    base: Params<L>,
+    space: Space,
+    search: Search,
+    save_path: String
+}
+
+impl<const L: usize> Tuner<L> {
+    /// Creates a `Tuner` of `space` around `base` parameters
+    ///
+    /// ## Note
+    /// Holds out `VALID_SPLIT` of the data unless `base` sets a `valid_split`
+    pub fn new(base: &Params<L>, space: Space, search: Search) -> Self {
+        let mut base = base.clone();
+        if base.valid_split == 0.0 {
+            base.valid_split = VALID_SPLIT;
+        }
+
+        for widths in space.widths.iter() {
+            assert_eq!(widths.len(), L-2, "widths must cover every hidden layer");
+        }
+        if !space.momentum.is_empty() {
+            assert!(
+                matches!(base.optim, Optim::Momentum(_) | Optim::Nesterov(_)),
+                "momentum is only searched for momentum based optimizers"
+            );
+        }
+
+        Self {
+            base,
+            space,
+            search,
+            save_path: SAVE_PATH.to_string()
+        }
+    }
+
+    /// Set leaderboard `save_path`
+    pub fn save_path(&mut self, save_path: &str) -> &mut Self {
+        self.save_path = save_path.to_string();
+        self
+    }
+
+    /// Runs the search on inputs `xs` and labels `ys`,
+    /// saving the leaderboard to `save_path`
+    /// 
+    /// ## Note
+    /// Every candidate is scored on the same held out split
+    pub fn run(&self, (xs, ys): (&[Mat], &[Mat])) -> Result<Leaderboard, Error> {
+        assert_eq!(xs.len(), ys.len());
+
+        let mut rng = self.base.seed.map_or_else(Pcg::from_entropy, Pcg::new);
+        let mut board = Leaderboard::default();
+
+        let mut indices: Vec<_> = (0..xs.len()).collect();
+        indices.shuffle(&mut rng);
+
+        let n_valid = (xs.len() as f32 * self.base.valid_split).round() as usize;
+        assert!(n_valid > 0 && n_valid < xs.len(), "too few samples to hold out a validation split");
+        let held_out = indices.split_off(xs.len() - n_valid);
+
+        let split = |indices: &[usize]| -> (Vec<Mat>, Vec<Mat>) {
+            indices.iter().map(|&i| (xs[i].clone(), ys[i].clone())).unzip()
+        };
+        let (train_xs, train_ys) = split(&indices);
+        let (valid_xs, valid_ys) = split(&held_out);
+        let (train, valid) = ((&train_xs[..], &train_ys[..]), (&valid_xs[..], &valid_ys[..]));
+
+        match self.search {
+            Search::Grid => {
+                for config in self.grid() {
+                    board.trials.push(self.trial(config, self.base.epochs, train, valid)?);
+                }
+            }
+            Search::Random(trials) => {
+                for _ in 0..trials {
+                    let config = self.sample(&mut rng);
+                    board.trials.push(self.trial(config, self.base.epochs, train, valid)?);
+                }
+            }
+            Search::Halving(candidates, factor) => {
+                assert!(factor > 1);
+                let mut configs: Vec<_> = (0..candidates).map(|_| self.sample(&mut rng)).collect();
+
+                // rounds until a single candidate survives
+                let mut rounds = 0;
+                let mut survivors = candidates;
+                while survivors > 1 {
+                    survivors = (survivors / factor).max(1);
+                    rounds += 1;
+                }
+
+                for round in 0..=rounds {
+                    // the final round trains for the full base epochs
+                    let epochs = (self.base.epochs * factor.pow(round) / factor.pow(rounds)).max(1);
+                    let mut trials = configs
+                        .into_iter()
+                        .map(|config| self.trial(config, epochs, train, valid))
+                        .collect::<Result<Vec<_>, _>>()?;
+
+                    trials.sort_by(|a, b| a.score.total_cmp(&b.score));
+
+                    let keep = (trials.len() / factor).max(1);
+                    configs = trials.iter().take(keep).map(|trial| trial.config.clone()).collect();
+
+                    // eliminated candidates keep their last score
+                    board.trials.extend(trials.into_iter().skip(if round == rounds { 0 } else { keep }));
+                }
+            }
+        }
+
+        board.rank();
+        board.save(&self.save_path)?;
+        Ok(board)
+    }
+
+    /// Trains `config` for `epochs` on the `train` set, scoring it on `valid`
+    fn trial(
+        &self, 
+        config: Config, 
+        epochs: usize, 
+        train: (&[Mat], &[Mat]), 
+        valid: (&[Mat], &[Mat])
+    ) -> Result<Trial, Error> {
+        let mut params = self.base.clone();
+        config.apply(&mut params);
+        params.epochs = epochs;
+        params.verbose = false;
+
+        let history = params.build().train(train, Some(valid))?;
+        let score = history.epochs
+            .iter()
+            .filter_map(|epoch| epoch.valid_loss)
+            .fold(f32::INFINITY, f32::min);
+        let accuracy = history.last().and_then(|epoch| epoch.valid_accuracy).unwrap_or(0.0);
+
+        if self.base.verbose {
+            let config = serde_json::to_string(&config).unwrap_or_default();
+            println!("trial {} over {} epochs scored {}", config, epochs, score);
+        }
+
+        Ok(Trial { config, epochs, score, accuracy })
+    }
+
+    /// Returns the base `Config`
+    fn base_config(&self) -> Config {
+        let momentum = match self.base.optim {
+            Optim::Momentum(m) | Optim::Nesterov(m) => Some(m),
+            _ => None
+        };
+
+        Config {
+            learn_rate: self.base.learn_rate,
+            momentum,
+            batch_size: self.base.batch_size,
+            widths: self.base.form[1..L-1].to_vec(),
+            act: self.base.act,
+            weight: self.base.weight
+        }
+    }
+
+    /// Returns every combination of the space
+    fn grid(&self) -> Vec<Config> {
+        let base = self.base_config();
+        let mut configs = vec![base];
+
+        // expands every config by each candidate of a field
+        fn expand<T: Clone>(configs: Vec<Config>, values: &[T], set: impl Fn(&mut Config, T)) -> Vec<Config> {
+            if values.is_empty() {
+                return configs
+            }
+
+            let set = &set;
+            configs
+                .into_iter()
+                .flat_map(|config| values.iter().map(move |value| {
+                    let mut config = config.clone();
+                    set(&mut config, value.clone());
+                    config
+                }).collect::<Vec<_>>())
+                .collect()
+        }
+
+        configs = expand(configs, &self.space.learn_rate, |c, v| c.learn_rate = v);
+        configs = expand(configs, &self.space.momentum, |c, v| c.momentum = Some(v));
+        configs = expand(configs, &self.space.batch_size, |c, v| c.batch_size = v);
+        configs = expand(configs, &self.space.widths, |c, v| c.widths = v);
+        configs = expand(configs, &self.space.act, |c, v| c.act = v);
+        configs = expand(configs, &self.space.weight, |c, v| c.weight = v);
+        configs
+    }
+
+    /// Samples a random combination of the space
+    fn sample(&self, rng: &mut Pcg) -> Config {
+        fn pick<T: Clone>(rng: &mut Pcg, values: &[T], base: T) -> T {
+            if values.is_empty() {
+                base
+            } else {
+                values[rng.gen_range(0..values.len())].clone()
+            }
+        }
+
+        let base = self.base_config();
+        let momentum = match self.space.momentum.is_empty() {
+            true => base.momentum,
+            false => Some(pick(rng, &self.space.momentum, 0.0))
+        };
+
+        Config {
+            learn_rate: pick(rng, &self.space.learn_rate, base.learn_rate),
+            momentum,
+            batch_size: pick(rng, &self.space.batch_size, base.batch_size),
+            widths: pick(rng, &self.space.widths, base.widths),
+            act: pick(rng, &self.space.act, base.act),
+            weight: pick(rng, &self.space.weight, base.weight)
+        }
+    }
+}
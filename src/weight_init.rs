@@ -1,33 +1,94 @@
 use serde::{Serialize, Deserialize};
 
-use crate::matrix::Mat;
+use crate::matrix::{Mat, MatBase};
 
 #[derive(Serialize, Deserialize, Clone, Copy)]
 pub enum Weight {
     Value(f32),
     Sqrt,
     Range(
-        f32, // min, 
+        f32, // min,
         f32 // max
     ),
     RangeNorm(
         f32, // min
         f32, // max
         f32, // normalize
+    ),
+    XavierUniform,
+    XavierNormal,
+    HeUniform,
+    HeNormal,
+    LeCunNormal,
+    Orthogonal(
+        f32 // gain
     )
 }
 
 impl Weight {
     /// Creates weights given nodes going `n_in` and `n_out`
     pub fn init(&self, n_in: usize, n_out: usize) -> Mat {
+        let (fan_in, fan_out) = (n_in as f32, n_out as f32);
+
         match self {
             Weight::Sqrt => {
-                let bounds = 1.0 / (n_in as f32).sqrt();
+                let bounds = 1.0 / fan_in.sqrt();
                 Mat::random((n_out, n_in), -bounds, bounds)
             }
             Weight::Value(n) => Mat::filled((n_out, n_in), *n),
             Weight::Range(min, max) => Mat::random((n_out, n_in), *min, *max),
-            Weight::RangeNorm(min, max, norm) => Mat::random((n_out, n_in), *min, *max).scale(1.0 / norm)
+            Weight::RangeNorm(min, max, norm) => Mat::random((n_out, n_in), *min, *max).scale(1.0 / norm),
+            Weight::XavierUniform => {
+                let bounds = (6.0 / (fan_in + fan_out)).sqrt();
+                Mat::random((n_out, n_in), -bounds, bounds)
+            }
+            Weight::XavierNormal => Mat::random_normal((n_out, n_in), 0.0, (2.0 / (fan_in + fan_out)).sqrt()),
+            Weight::HeUniform => {
+                let bounds = (6.0 / fan_in).sqrt();
+                Mat::random((n_out, n_in), -bounds, bounds)
+            }
+            Weight::HeNormal => Mat::random_normal((n_out, n_in), 0.0, (2.0 / fan_in).sqrt()),
+            Weight::LeCunNormal => Mat::random_normal((n_out, n_in), 0.0, (1.0 / fan_in).sqrt()),
+            Weight::Orthogonal(gain) => orthogonal(n_in, n_out, *gain)
         }
     }
-}
\ No newline at end of file
+}
+
+/// Creates a `gain` scaled weight matrix with orthonormal rows or columns,
+/// whichever there are fewer of
+///
+/// ## Note
+/// Orthonormalizes a normal sample with Gram-Schmidt
+fn orthogonal(n_in: usize, n_out: usize, gain: f32) -> Mat {
+    let (n, len) = (n_in.min(n_out), n_in.max(n_out));
+    let sample = Mat::random_normal((n, len), 0.0, 1.0);
+
+    let mut basis: Vec<Vec<f32>> = sample
+        .data()
+        .chunks(len)
+        .map(|v| v.to_vec())
+        .collect();
+
+    for i in 0..n {
+        let (prev, rest) = basis.split_at_mut(i);
+        let v = &mut rest[0];
+
+        for q in prev.iter() {
+            let dot: f32 = v.iter().zip(q).map(|(a, b)| a * b).sum();
+            v.iter_mut().zip(q).for_each(|(a, b)| *a -= dot * b);
+        }
+
+        let norm = v.iter().map(|a| a * a).sum::<f32>().sqrt();
+        v.iter_mut().for_each(|a| *a /= norm);
+    }
+
+    // rows hold the basis when there are fewer outputs, columns otherwise
+    let buf = (0..n_out*n_in)
+        .map(|i| {
+            let (row, col) = (i / n_in, i % n_in);
+            gain * if n_out <= n_in { basis[row][col] } else { basis[col][row] }
+        })
+        .collect();
+
+    Mat::from_vec((n_out, n_in), buf)
+}